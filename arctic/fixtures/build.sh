@@ -0,0 +1,11 @@
+# /bin/bash
+# Rebuilds the DLLs `memory::export`'s tests read, without needing Windows or a Windows toolchain.
+set -e
+cd "$(dirname "$0")"
+LLD="$(rustc +nightly --print sysroot)/lib/rustlib/$(rustc +nightly -vV | sed -n 's/host: //p')/bin/rust-lld"
+for target in x86:32:i686 x64:64:x86_64; do
+    IFS=: read -r machine bits arch <<< "$target"
+    rustc +nightly --target "$arch-pc-windows-msvc" --emit obj -O -C panic=abort -o "fixture$bits.obj" fixture.rs
+    "$LLD" -flavor link /dll /noentry /nodefaultlib /brepro /machine:"$machine" /def:fixture.def /implib:/dev/null /out:"fixture$bits.dll" "fixture$bits.obj"
+    rm "fixture$bits.obj"
+done
@@ -0,0 +1,26 @@
+//! Source of the fixture DLLs, see `build.sh`. It has no core library, so that it builds for any
+//! target without installing one.
+
+#![feature(no_core, lang_items)]
+#![allow(internal_features)]
+#![no_core]
+#![crate_type = "lib"]
+
+#[lang = "pointee_sized"]
+trait PointeeSized {}
+
+#[lang = "meta_sized"]
+trait MetaSized: PointeeSized {}
+
+#[lang = "sized"]
+trait Sized: MetaSized {}
+
+#[no_mangle]
+pub extern "C" fn arctic_fixture_answer() -> i32 {
+    42
+}
+
+#[no_mangle]
+pub extern "C" fn arctic_fixture_hidden() -> i32 {
+    7
+}
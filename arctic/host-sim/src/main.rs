@@ -0,0 +1,277 @@
+use arctic_template::{abi::*, functions::DNXFunctions, mock::*};
+use libloading::Library;
+use std::{collections::BTreeMap, path::PathBuf, process::exit, time::Duration};
+
+/// Signature of the payload's exported entry point.
+type ArcticGateway = unsafe extern "C" fn(ProcessHandle, ModuleHandle, *const DNXFunctions);
+
+const USAGE: &str = "Usage: arctic-host-sim [library] [--ticks N] [--delta-time SECONDS] \
+[--set-thread-key NAME@TICK]... [--verbose]";
+
+/// Command-line options.
+struct Options {
+    /// Path to the compiled payload.
+    library: PathBuf,
+
+    /// How many ticks to run for at most, before giving up on the payload ejecting itself.
+    ticks: u32,
+
+    /// Delta-time handed out through `dynamic_get_delta_time`, and also the time between ticks.
+    delta_time: f32,
+
+    /// Thread-keys to set to `true` at the given tick, for triggering auto-eject listeners.
+    thread_keys: Vec<(String, u32)>,
+
+    /// Whether to also print calls which only read from the host.
+    verbose: bool,
+}
+
+impl Options {
+    /// Parses the options from the command-line arguments.
+    fn parse() -> Result<Self, String> {
+        let mut options = Self {
+            library: PathBuf::from("target/debug/libarctic_template.so"),
+            ticks: 600,
+            delta_time: 1.0 / 60.0,
+            thread_keys: Vec::new(),
+            verbose: false,
+        };
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or(format!("{name} needs a value"));
+            match arg.as_str() {
+                "--ticks" => {
+                    options.ticks = value("--ticks")?
+                        .parse()
+                        .map_err(|error| format!("Invalid --ticks, {error}"))?
+                }
+                "--delta-time" => {
+                    options.delta_time = value("--delta-time")?
+                        .parse()
+                        .map_err(|error| format!("Invalid --delta-time, {error}"))?
+                }
+                "--set-thread-key" => {
+                    let value = value("--set-thread-key")?;
+                    let (name, tick) = value
+                        .rsplit_once('@')
+                        .ok_or("--set-thread-key expects NAME@TICK")?;
+                    let tick = tick
+                        .parse()
+                        .map_err(|error| format!("Invalid --set-thread-key tick, {error}"))?;
+                    options.thread_keys.push((name.to_owned(), tick));
+                }
+                "--verbose" => options.verbose = true,
+                "--help" | "-h" => {
+                    println!("{USAGE}");
+                    exit(0);
+                }
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {arg}")),
+                _ => options.library = PathBuf::from(arg),
+            }
+        }
+
+        if !options.delta_time.is_finite() || options.delta_time <= 0.0 {
+            return Err("--delta-time has to be a positive number".to_owned());
+        }
+
+        Ok(options)
+    }
+}
+
+/// Simulated UI state, rebuilt from the calls the payload makes.
+#[derive(Default)]
+struct UIState {
+    /// Every window and the widgets in it, in the order they were added.
+    windows: BTreeMap<String, Vec<String>>,
+
+    /// The window which new widgets are added to.
+    focused: Option<String>,
+}
+
+impl UIState {
+    /// Applies `call` to the UI, if it modifies it.
+    fn apply(&mut self, call: &Call) {
+        let widget = match call {
+            Call::AddWindow(name) => {
+                self.windows.entry(name.clone()).or_default();
+                self.focused = Some(name.clone());
+                return;
+            }
+            Call::FocusWindow(name) => {
+                if self.windows.contains_key(name) {
+                    self.focused = Some(name.clone());
+                }
+
+                return;
+            }
+            Call::RemoveWidget(identifier) => {
+                if let Some(widgets) = self.focused_widgets() {
+                    widgets.retain(|widget| !widget.starts_with(&format!("{identifier}:")));
+                }
+
+                return;
+            }
+            Call::RemoveAllWidgets => {
+                if let Some(widgets) = self.focused_widgets() {
+                    widgets.clear();
+                }
+
+                return;
+            }
+            Call::AddLabel {
+                identifier, text, ..
+            } => format!("{identifier}: label {text:?}"),
+            Call::AddCustomFontLabel {
+                identifier,
+                text,
+                relative_font_path,
+            } => format!("{identifier}: label {text:?} ({relative_font_path})"),
+            Call::AddButton {
+                identifier, text, ..
+            } => format!("{identifier}: button {text:?}"),
+            Call::AddSeparator(identifier) => format!("{identifier}: separator"),
+            Call::AddSpacing { identifier, x, y } => format!("{identifier}: spacing {x}x{y}"),
+            Call::AddI32Slider {
+                identifier,
+                text,
+                min,
+                max,
+                ..
+            } => format!("{identifier}: i32 slider {text:?} {min}..={max}"),
+            Call::AddF32Slider {
+                identifier,
+                text,
+                min,
+                max,
+                ..
+            } => format!("{identifier}: f32 slider {text:?} {min}..={max}"),
+            _ => return,
+        };
+
+        let Some(widgets) = self.focused_widgets() else {
+            println!("[sim] Widget added without a focused window: {widget}");
+            return;
+        };
+
+        widgets.push(widget);
+    }
+
+    /// Widgets of the focused window.
+    fn focused_widgets(&mut self) -> Option<&mut Vec<String>> {
+        self.windows.get_mut(self.focused.as_ref()?)
+    }
+
+    /// Prints every window and its widgets.
+    fn print(&self) {
+        println!("[sim] UI state:");
+        if self.windows.is_empty() {
+            println!("  (no windows)");
+        }
+
+        for (name, widgets) in &self.windows {
+            let focused = if self.focused.as_ref() == Some(name) {
+                " (focused)"
+            } else {
+                ""
+            };
+
+            println!("  window {name:?}{focused}");
+            for widget in widgets {
+                println!("    {widget}");
+            }
+        }
+    }
+}
+
+/// Checks whether `call` only reads from the host, such as polling a thread-key.
+fn is_read(call: &Call) -> bool {
+    matches!(
+        call,
+        Call::GetDeltaTime
+            | Call::GetThreadKey(_)
+            | Call::GetI32SliderValue(_)
+            | Call::GetF32SliderValue(_)
+            | Call::MemoryReadString(_)
+    )
+}
+
+/// Prints every call made since the last flush, and applies them to the UI.
+fn flush(host: &MockHost, ui: &mut UIState, tick: u32, verbose: bool) {
+    for call in host.take_calls() {
+        ui.apply(&call);
+        if verbose || !is_read(&call) {
+            match call {
+                Call::Log(message) => println!("[{tick:>5}] log: {message}"),
+                call => println!("[{tick:>5}] {call:?}"),
+            }
+        }
+    }
+}
+
+fn main() {
+    let options = Options::parse().unwrap_or_else(|error| {
+        eprintln!("{error}\n{USAGE}");
+        exit(1);
+    });
+
+    let host = MockHost::new();
+    host.set_delta_time(options.delta_time);
+
+    // SAFETY: Loading the payload runs its initializers, which is exactly what we want to test.
+    let library = unsafe { Library::new(&options.library) }.unwrap_or_else(|error| {
+        eprintln!(
+            "Failed loading {}, error: {error}",
+            options.library.display()
+        );
+        exit(1);
+    });
+
+    // SAFETY: The signature matches the one of `arctic_template::arctic_gateway`, and the
+    // pointer isn't used past the point where the library is unloaded.
+    let arctic_gateway = *unsafe { library.get::<ArcticGateway>(b"arctic_gateway\0") }
+        .unwrap_or_else(|error| {
+            eprintln!("Failed resolving arctic_gateway, error: {error}");
+            exit(1);
+        });
+
+    println!(
+        "[sim] Calling arctic_gateway in {}",
+        options.library.display()
+    );
+    // SAFETY: The table is owned by `host`, which outlives the library.
+    unsafe { arctic_gateway(MOCK_PROCESS, MOCK_PAYLOAD, host.table()) };
+
+    let mut ui = UIState::default();
+    let mut tick = 0;
+    flush(&host, &mut ui, tick, options.verbose);
+    while !host.ejected() && tick < options.ticks {
+        tick += 1;
+        for (name, _) in options.thread_keys.iter().filter(|(_, at)| *at == tick) {
+            println!("[{tick:>5}] Setting thread-key {name:?} to true");
+            host.set_thread_key(name, true);
+        }
+
+        std::thread::sleep(Duration::from_secs_f32(options.delta_time));
+        flush(&host, &mut ui, tick, options.verbose);
+    }
+
+    ui.print();
+    if !host.ejected() {
+        println!("[sim] Payload is still loaded after {tick} ticks, leaving it loaded");
+        // Unloading a payload which never asked for it would pull the code out from under its
+        // threads.
+        std::mem::forget(library);
+        return;
+    }
+
+    // Give the thread which requested the ejection a moment to return before unloading the code
+    // it's running.
+    std::thread::sleep(Duration::from_millis(100));
+    flush(&host, &mut ui, tick, options.verbose);
+    println!("[sim] Payload requested ejection at tick {tick}, unloading it");
+    if let Err(error) = library.close() {
+        eprintln!("Failed unloading the payload, error: {error}");
+        exit(1);
+    }
+}
@@ -0,0 +1,135 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Ident, ItemFn};
+
+/// Turns a function into the payload's main function.
+/// The function has to take a single `&Gateway` and return either `()` or a `Payload`, for
+/// example:
+/// ```ignore
+/// #[arctic::gateway]
+/// fn main(ctx: &Gateway) {
+///     log!("Hello from the payload!");
+/// }
+/// ```
+/// This generates the exported `arctic_gateway` symbol, which checks the host's function table,
+/// stores it in `FUNCTIONS`, and runs the function with panics contained.
+/// Functions returning `()` are ejected once they return, unless `Gateway::stay_resident` has been
+/// called, while returned `Payload`s are driven until they're ejected.
+/// `#[arctic::gateway(hot_reload)]` also generates the `arctic_hot_*` symbols, through which a
+/// hot-reload shim drives the payload instead, see `HotReload`. Builds which are injected directly
+/// don't need them.
+#[proc_macro_attribute]
+pub fn gateway(attribute: TokenStream, item: TokenStream) -> TokenStream {
+    let hot_reload = match parse_arguments(attribute) {
+        Ok(hot_reload) => hot_reload,
+        Err(error) => return error.to_compile_error().into(),
+    };
+
+    let main = parse_macro_input!(item as ItemFn);
+    if let Err(error) = validate(&main) {
+        return error.to_compile_error().into();
+    }
+
+    let ident = &main.sig.ident;
+    let hot_reload = hot_reload.then(|| hot_reload_exports(ident));
+    quote! {
+        #main
+
+        /// This is the Arctic Gateway main function, generated by `#[arctic::gateway]`.
+        ///
+        /// # Safety
+        /// `functions` has to point to a table which starts with a `DNXHeader`, and which lives
+        /// until the payload has been ejected.
+        #[no_mangle]
+        pub unsafe extern "C" fn arctic_gateway(
+            process: ::arctic::abi::ProcessHandle,
+            payload: ::arctic::abi::ModuleHandle,
+            functions: *const ::arctic::functions::DNXFunctions,
+        ) {
+            ::arctic::gateway::run(process, payload, functions, #ident);
+        }
+
+        #hot_reload
+    }
+    .into()
+}
+
+/// Parses the arguments of `#[arctic::gateway]`, returning whether `hot_reload` has been passed.
+fn parse_arguments(attribute: TokenStream) -> syn::Result<bool> {
+    if attribute.is_empty() {
+        return Ok(false);
+    }
+
+    let argument = syn::parse::<Ident>(attribute)?;
+    if argument != "hot_reload" {
+        return Err(syn::Error::new(
+            argument.span(),
+            "#[arctic::gateway] only takes `hot_reload`",
+        ));
+    }
+
+    Ok(true)
+}
+
+/// The `arctic_hot_*` symbols, through which a hot-reload shim drives the payload.
+fn hot_reload_exports(ident: &Ident) -> proc_macro2::TokenStream {
+    quote! {
+        /// Boots the payload under a hot-reload shim, see `#[arctic::gateway(hot_reload)]`.
+        ///
+        /// # Safety
+        /// Same as `arctic_gateway`, and `state` has to be null or valid for the duration of the
+        /// call.
+        #[no_mangle]
+        pub unsafe extern "C" fn arctic_hot_boot(
+            process: ::arctic::abi::ProcessHandle,
+            payload: ::arctic::abi::ModuleHandle,
+            functions: *const ::arctic::functions::DNXFunctions,
+            state: ::arctic::abi::FfiBytes,
+        ) -> bool {
+            ::arctic::hot_reload::boot(process, payload, functions, state, #ident)
+        }
+
+        /// Ticks the payload under a hot-reload shim, see `#[arctic::gateway(hot_reload)]`.
+        #[no_mangle]
+        pub extern "C" fn arctic_hot_tick(delta_time: f32) -> bool {
+            ::arctic::hot_reload::tick(delta_time)
+        }
+
+        /// Unloads the payload under a hot-reload shim, see `#[arctic::gateway(hot_reload)]`.
+        #[no_mangle]
+        pub extern "C" fn arctic_hot_unload(
+            context: *mut ::std::ffi::c_void,
+            sink: ::arctic::hot_reload::StateSink,
+        ) -> usize {
+            ::arctic::hot_reload::unload(context, sink)
+        }
+    }
+}
+
+/// Makes sure `main` can be called as `fn(&Gateway) -> R`, so that mistakes are reported on the
+/// function itself rather than somewhere in the generated code.
+fn validate(main: &ItemFn) -> syn::Result<()> {
+    let signature = &main.sig;
+    if let Some(asyncness) = &signature.asyncness {
+        return Err(syn::Error::new(
+            asyncness.span(),
+            "the payload's main function can't be async",
+        ));
+    }
+
+    if !signature.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            signature.generics.span(),
+            "the payload's main function can't be generic",
+        ));
+    }
+
+    if signature.inputs.len() != 1 {
+        return Err(syn::Error::new(
+            signature.inputs.span(),
+            "the payload's main function has to take a single `&Gateway`",
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,232 @@
+use std::{
+    borrow::Cow,
+    ffi::c_void,
+    fmt::{Arguments, Display},
+};
+
+/// Version of the function table layout.
+/// Bump this whenever an existing field of `DNXFunctions` changes its type, position or meaning.
+/// Appending new functions doesn't need a bump, since the size in the header tells the payload
+/// which functions the host knows about.
+pub const ABI_VERSION: u32 = 2;
+
+/// Header placed at the very start of `DNXFunctions`.
+/// The header and the `dynamic_log` field right after it are frozen, so that a payload can always
+/// report an ABI mismatch to the host, regardless of how far apart the two versions are.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct DNXHeader {
+    /// ABI version the host was built against.
+    pub version: u32,
+
+    /// Size of the host's function table in bytes, header included.
+    pub size: u32,
+}
+
+impl DNXHeader {
+    /// Header describing the function table layout of this build.
+    pub const fn current<T>() -> Self {
+        Self {
+            version: ABI_VERSION,
+            size: std::mem::size_of::<T>() as u32,
+        }
+    }
+}
+
+/// Frozen prefix of `DNXFunctions`, see `DNXHeader`.
+#[repr(C)]
+pub(crate) struct DNXPrefix {
+    pub header: DNXHeader,
+    pub dynamic_log: extern "C" fn(FfiStr),
+}
+
+/// Borrowed UTF-8 string passed across the DLL boundary.
+/// A null pointer is used for `None` where a parameter is optional.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct FfiStr {
+    ptr: *const u8,
+    len: usize,
+}
+
+impl FfiStr {
+    /// Borrows `string` for the duration of a call.
+    pub const fn new(string: &str) -> Self {
+        Self {
+            ptr: string.as_ptr(),
+            len: string.len(),
+        }
+    }
+
+    /// Null string, used for `None`.
+    pub const fn null() -> Self {
+        Self {
+            ptr: std::ptr::null(),
+            len: 0,
+        }
+    }
+
+    /// Borrows `string` if present, otherwise returns a null string.
+    pub fn from_option(string: Option<&str>) -> Self {
+        string.map_or_else(Self::null, Self::new)
+    }
+
+    /// Returns `true` if this is a null string.
+    pub fn is_null(&self) -> bool {
+        self.ptr.is_null()
+    }
+
+    /// Attempts to view the string as `&str`.
+    /// Returns `None` if it's null or not valid UTF-8.
+    ///
+    /// # Safety
+    /// The pointer must be valid for `len` bytes for the entirety of `'a`.
+    pub unsafe fn as_str<'a>(&self) -> Option<&'a str> {
+        if self.is_null() {
+            return None;
+        }
+
+        std::str::from_utf8(std::slice::from_raw_parts(self.ptr, self.len)).ok()
+    }
+}
+
+impl From<&str> for FfiStr {
+    fn from(string: &str) -> Self {
+        Self::new(string)
+    }
+}
+
+/// Text which can be handed to the host, accepted by `log!` and the text parameters of every host
+/// function macro.
+/// `format_args!` is only formatted into a `String` if it has arguments, since the host needs the
+/// whole string at once.
+pub trait HostStr {
+    /// Borrows or formats the text.
+    fn host_str(&self) -> Cow<'_, str>;
+}
+
+impl HostStr for str {
+    fn host_str(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl HostStr for String {
+    fn host_str(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl HostStr for Cow<'_, str> {
+    fn host_str(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl HostStr for Arguments<'_> {
+    fn host_str(&self) -> Cow<'_, str> {
+        self.as_str()
+            .map_or_else(|| Cow::Owned(self.to_string()), Cow::Borrowed)
+    }
+}
+
+impl<T: HostStr + ?Sized> HostStr for &T {
+    fn host_str(&self) -> Cow<'_, str> {
+        (**self).host_str()
+    }
+}
+
+/// Borrowed bytes passed across the DLL boundary, used for handing state over on a hot reload.
+/// A null pointer is used for `None`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct FfiBytes {
+    ptr: *const u8,
+    len: usize,
+}
+
+impl FfiBytes {
+    /// Borrows `bytes` if present, otherwise returns null bytes.
+    pub fn from_option(bytes: Option<&[u8]>) -> Self {
+        bytes.map_or(
+            Self {
+                ptr: std::ptr::null(),
+                len: 0,
+            },
+            Self::from,
+        )
+    }
+
+    /// Attempts to view the bytes as `&[u8]`.
+    /// Returns `None` if they're null.
+    ///
+    /// # Safety
+    /// The pointer must be valid for `len` bytes for the entirety of `'a`.
+    pub unsafe fn as_slice<'a>(&self) -> Option<&'a [u8]> {
+        if self.ptr.is_null() {
+            return None;
+        }
+
+        Some(std::slice::from_raw_parts(self.ptr, self.len))
+    }
+}
+
+impl From<&[u8]> for FfiBytes {
+    fn from(bytes: &[u8]) -> Self {
+        Self {
+            ptr: bytes.as_ptr(),
+            len: bytes.len(),
+        }
+    }
+}
+
+/// Native callback invoked by the host when a button is clicked.
+pub type ButtonCallback = Option<extern "C" fn()>;
+
+/// Raw handle to the host process, `HANDLE` on Windows.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProcessHandle(pub *mut c_void);
+
+/// Raw handle to the injected payload module, `HMODULE` on Windows.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ModuleHandle(pub *mut c_void);
+
+// SAFETY: Both handles are opaque values owned by the host, they're never dereferenced by the
+// payload and only ever handed back to the host.
+unsafe impl Send for ProcessHandle {}
+unsafe impl Sync for ProcessHandle {}
+unsafe impl Send for ModuleHandle {}
+unsafe impl Sync for ModuleHandle {}
+
+/// Reason why the host's function table can't be used by this payload.
+#[derive(Debug)]
+pub enum AbiMismatch {
+    /// The host passed a null function table.
+    Null,
+
+    /// The host was built against a different ABI version.
+    Version { host: u32, payload: u32 },
+
+    /// The host's function table is too small to even hold the frozen prefix.
+    TooSmall { host: u32, minimum: u32 },
+}
+
+impl Display for AbiMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => write!(f, "host passed a null function table"),
+            Self::Version { host, payload } => write!(
+                f,
+                "ABI version mismatch, host is on v{host} while the payload is on v{payload}"
+            ),
+            Self::TooSmall { host, minimum } => write!(
+                f,
+                "function table is {host} bytes large, while at least {minimum} bytes are needed"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AbiMismatch {}
@@ -0,0 +1,31 @@
+use std::fmt::Display;
+
+pub use crate::functions::Capability;
+
+impl Capability {
+    /// Looks up a capability by the name of its host function.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|capability| capability.name() == name)
+    }
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Returned when calling a host function which the host doesn't provide.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Unsupported(pub Capability);
+
+impl Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "host function `{}` isn't supported by this host", self.0)
+    }
+}
+
+impl std::error::Error for Unsupported {}
@@ -0,0 +1,103 @@
+use crate::capabilities::{Capability, Unsupported};
+use std::fmt::Display;
+
+/// Error returned by the fallible API in `arctic::api`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GatewayError {
+    /// The gateway hasn't been started yet, so there's no function table to call into.
+    NotInitialized,
+
+    /// The host doesn't provide the function.
+    Unsupported(Capability),
+
+    /// A name is empty or contains a null byte, neither of which the host can handle.
+    InvalidIdentifier(String),
+
+    /// Text, such as a label or script, contains a null byte, which the host can't handle.
+    InvalidText(String),
+
+    /// The host failed to carry out the call.
+    Host(String),
+}
+
+impl Display for GatewayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotInitialized => write!(f, "the gateway hasn't been initialized yet"),
+            Self::Unsupported(capability) => Unsupported(*capability).fmt(f),
+            Self::InvalidIdentifier(identifier) => write!(f, "invalid identifier {identifier:?}"),
+            Self::InvalidText(text) => write!(f, "invalid text {text:?}"),
+            Self::Host(message) => write!(f, "host-side failure, {message}"),
+        }
+    }
+}
+
+impl std::error::Error for GatewayError {}
+
+impl From<Unsupported> for GatewayError {
+    fn from(unsupported: Unsupported) -> Self {
+        Self::Unsupported(unsupported.0)
+    }
+}
+
+/// Makes sure `identifier` can be handed to the host as a name.
+pub(crate) fn validate_identifier(identifier: &str) -> Result<(), GatewayError> {
+    if identifier.is_empty() || identifier.contains('\0') {
+        return Err(GatewayError::InvalidIdentifier(identifier.to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Makes sure `text` can be handed to the host, where it may be empty.
+pub(crate) fn validate_text(text: &str) -> Result<(), GatewayError> {
+    if text.contains('\0') {
+        return Err(GatewayError::InvalidText(text.to_owned()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api, mock::MockHost};
+
+    #[test]
+    fn names_have_to_be_non_empty() {
+        assert!(validate_identifier("window").is_ok());
+        assert!(validate_identifier("").is_err());
+        assert!(validate_identifier("win\0dow").is_err());
+        assert!(validate_text("").is_ok());
+        assert!(validate_text("te\0xt").is_err());
+    }
+
+    #[test]
+    fn invalid_arguments_never_reach_the_host() {
+        let host = MockHost::new();
+        host.install().unwrap();
+
+        let invalid = |string: &str| Err(GatewayError::InvalidIdentifier(string.to_owned()));
+        let invalid_text = |string: &str| Err(GatewayError::InvalidText(string.to_owned()));
+        assert_eq!(api::ui_add_window(""), invalid(""));
+        assert_eq!(api::ui_focus_window("a\0b"), invalid("a\0b"));
+        assert_eq!(api::dynamic_add_thread_key(""), invalid(""));
+        assert_eq!(
+            api::pxscript_execute("a\0b", false, false),
+            invalid_text("a\0b")
+        );
+        assert_eq!(api::rune_vm_execute("\0"), invalid_text("\0"));
+        assert_eq!(api::ui_add_label("label", "a\0b", 0), invalid_text("a\0b"));
+        assert_eq!(
+            api::ui_add_i32_slider("slider", "Speed", 0, 10, Some("\0")),
+            invalid_text("\0")
+        );
+        assert!(host.calls().is_empty());
+
+        assert_eq!(api::ui_add_label("label", "", 0), Ok(()));
+        assert_eq!(api::ui_add_button("button", "Click", "", None), Ok(()));
+        assert_eq!(api::ui_add_i32_slider("slider", "", 0, 10, None), Ok(()));
+        assert_eq!(api::pxscript_execute("", false, false), Ok(()));
+        assert_eq!(host.calls().len(), 4);
+    }
+}
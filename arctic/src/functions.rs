@@ -1,261 +1,301 @@
-use dll_syringe::process::*;
-use std::sync::{Arc, OnceLock};
+use crate::abi::*;
+use std::sync::atomic::{AtomicPtr, Ordering};
 
 /// A set of useful functions from dynamic.
-pub static FUNCTIONS: OnceLock<Arc<DNXFunctions>> = OnceLock::new();
+/// This is the payload's own copy of the host's table, see `DNXFunctions::from_raw`.
+pub static FUNCTIONS: FunctionTable = FunctionTable::new();
 
-/// A structure that contains a set of functions from dynamic.
-#[allow(dead_code)]
-#[allow(clippy::type_complexity)]
-pub struct DNXFunctions {
-    /// `dynamic::log(message)` function. Logs both to the side-messages, and to `stdout`.
-    dynamic_log: extern "Rust" fn(&str),
+/// Slot holding the validated function table, see `FUNCTIONS`.
+pub struct FunctionTable(AtomicPtr<DNXFunctions>);
+
+impl FunctionTable {
+    const fn new() -> Self {
+        Self(AtomicPtr::new(std::ptr::null_mut()))
+    }
+
+    /// The function table, `None` if the gateway hasn't started yet.
+    pub fn get(&self) -> Option<&'static DNXFunctions> {
+        // SAFETY: Only leaked tables are ever stored.
+        unsafe { self.0.load(Ordering::Acquire).as_ref() }
+    }
 
+    /// Stores `functions`, unless a table has been stored already.
+    pub(crate) fn set(&self, functions: &'static DNXFunctions) {
+        let _ = self.0.compare_exchange(
+            std::ptr::null_mut(),
+            std::ptr::from_ref(functions).cast_mut(),
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        );
+    }
+
+    /// Stores `functions` in place of whatever table has been stored, so that each `MockHost` is
+    /// seen with its own header.
+    #[cfg(any(test, feature = "mock-host"))]
+    pub(crate) fn replace_for_test(&self, functions: &'static DNXFunctions) {
+        self.0
+            .store(std::ptr::from_ref(functions).cast_mut(), Ordering::Release);
+    }
+
+    /// Forgets the stored table, as if the gateway had never started.
+    #[cfg(any(test, feature = "mock-host"))]
+    pub(crate) fn clear(&self) {
+        self.0.store(std::ptr::null_mut(), Ordering::Release);
+    }
+}
+
+// Every host function apart from `dynamic_log`, in the order they appear in the function table.
+// New functions must be appended to the end, see `define_host_functions!` for the syntax.
+define_host_functions! {
     /// `Memory::read_string(address) function. Attempts to read a string at `address`.
-    memory_read_string: extern "Rust" fn(i64) -> &'static str,
+    /// Returns an empty string if nothing could be read, or if it isn't valid UTF-8, see
+    /// `memory::read_c_string` and the like for reads which report why they failed.
+    fn memory_read_string(address: address) -> str => MemoryReadString, memory_read_string!;
 
     /// `PXScript::execute(code, send_to_party, use_main_thread)` function. Executes a script and
     /// optionally sends it to the party.
-    pxscript_execute: extern "Rust" fn(String, bool, bool),
+    fn pxscript_execute(code: text, send_to_party: bool, use_main_thread: bool) => PXScriptExecute, pxscript_execute!;
 
     /// `dynamic::get_delta_time()` function. Gets the current delta-time of the process.
-    dynamic_get_delta_time: extern "Rust" fn() -> f32,
+    fn dynamic_get_delta_time() -> f32 => DynamicGetDeltaTime, get_delta_time!;
 
     /// Special function for making dynamic eject the DLL, rather than the other way around.
     /// This is needed because otherwise the process crashes.
-    dynamic_eject_payload: extern "Rust" fn(OwnedProcess, ProcessModule<BorrowedProcess<'static>>),
+    fn dynamic_eject_payload(process: ProcessHandle, payload: ModuleHandle) => DynamicEjectPayload, eject_payload!;
 
     /// Rune VM which you may use to execute Rune code.
-    rune_vm_execute: Box<dyn Fn(String) + Send + Sync>,
+    fn rune_vm_execute(source: text) => RuneVMExecute, rune_vm_execute!;
 
     /// `dynamic::create_thread_key(name)` function. Creates a globally-accessible thread-key.
-    dyamic_add_thread_key: extern "Rust" fn(String),
+    fn dynamic_add_thread_key(identifier: str) => DynamicAddThreadKey, create_thread_key!;
 
     /// `dynamic::set_thread_key_value(name, value)` function. Sets the value of a thread-key.
-    dynamic_set_thread_key_value: extern "Rust" fn(String, bool),
+    fn dynamic_set_thread_key_value(identifier: str, value: bool) => DynamicSetThreadKeyValue, set_thread_key_value!;
 
     /// `dynamic::get_thread_key(name)` function. Returns the value of the thread-key.
-    dynamic_get_thread_key: extern "Rust" fn(String) -> bool,
+    fn dynamic_get_thread_key(identifier: str) -> bool => DynamicGetThreadKey, get_thread_key!;
 
     /// `ui::add_window(name)` function. Allocates and displays a new custom window.
-    ui_add_window: Box<dyn Fn(String) + Send + Sync>,
+    fn ui_add_window(identifier: str) => UIAddWindow, ui_add_window!;
 
     /// `ui::focus_window` function. Focuses the defined window if present.
-    ui_focus_window: Box<dyn Fn(String) + Send + Sync>,
+    fn ui_focus_window(identifier: str) => UIFocusWindow, ui_focus_window!;
 
     /// `ui::add_label(identifier, text)` function. Creates a new label with the specified content.
-    ui_add_label: Box<dyn Fn(String, String, usize) + Send + Sync>,
+    fn ui_add_label(identifier: str, text: text, font_id: usize) => UIAddLabel, ui_add_label!;
 
     /// `ui::add_button(identifier, text, code)` function. Creates a new button with the specified
     /// text and Rune code.
-    ui_add_button: Box<dyn Fn(String, String, String, Option<extern "Rust" fn()>) + Send + Sync>,
+    fn ui_add_button(identifier: str, text: text, source: text, callback: ButtonCallback) => UIAddButton, ui_add_button!;
 
     /// `ui::add_separator(identifier)` function. Adds a new horizontal separator.
-    ui_add_separator: Box<dyn Fn(String) + Send + Sync>,
+    fn ui_add_separator(identifier: str) => UIAddSeparator, ui_add_separator!;
 
     /// `ui::add_spacing(identifier, x, y)` function. Adds spacing between widgets.
-    ui_add_spacing: Box<dyn Fn(String, f32, f32) + Send + Sync>,
+    fn ui_add_spacing(identifier: str, x: f32, y: f32) => UIAddSpacing, ui_add_spacing!;
 
     /// `ui::get_i32_slider_value(identifier)` function. Returns the i32 value of a defined slider.
-    ui_get_i32_slider_value: Box<dyn Fn(String) -> i32 + Send + Sync>,
+    fn ui_get_i32_slider_value(identifier: str) -> i32 => UIGetI32SliderValue, get_i32_slider_value!;
 
     /// `ui::get_f32_slider_value(identifier)` function. Returns the f32 value of a defined slider.
-    ui_get_f32_slider_value: Box<dyn Fn(String) -> f32 + Send + Sync>,
+    fn ui_get_f32_slider_value(identifier: str) -> f32 => UIGetF32SliderValue, get_f32_slider_value!;
 
     /// `ui::set_next_item_same_line(identifier)` function. Attempts to make the next upcoming
     /// widget on the currently-active line.
-    ui_set_next_item_same_line: Box<dyn Fn(String) + Send + Sync>,
+    fn ui_set_next_item_same_line(identifier: str) => UISetNextItemSameLine, ui_set_next_item_same_line!;
 
     /// `ui::add_custom_font_label(identifier, text, relative_font_path)` function. Adds a new
     /// label with a custom-loaded font.
-    ui_add_custom_font_label: Box<dyn Fn(String, String, String) + Send + Sync>,
+    fn ui_add_custom_font_label(identifier: str, text: text, relative_font_path: str) => UIAddCustomFontLabel, ui_add_custom_font_label!;
 
     /// `ui::remove_widget(identifier)` function. Attempts to remove the specified widget from the
     /// focused window.
-    ui_remove_widget: Box<dyn Fn(String) + Send + Sync>,
+    fn ui_remove_widget(identifier: str) => UIRemoveWidget, ui_remove_widget!;
 
     /// `ui::remove_all_widgets()` function. Removes all widgets from the focused window.
-    ui_remove_all_widgets: Box<dyn Fn() + Send + Sync>,
+    fn ui_remove_all_widgets() => UIRemoveAllWidgets, ui_remove_all_widgets!;
 
     /// `ui::add_i32_slider(identifier, text, min, max, rune_code)` function. Adds a i32 slider to
     /// the UI with optional Rune code execution.
-    ui_add_i32_slider: Box<dyn Fn(String, String, i32, i32, Option<String>) + Send + Sync>,
+    fn ui_add_i32_slider(identifier: str, text: text, min: i32, max: i32, rune_code: opt_str) => UIAddI32Slider, ui_add_i32_slider!;
 
     /// `ui::add_f32_slider(identifier, text, min, max, rune_code)` function. Adds a f32 slider to
     /// the UI with optional Rune code execution.
-    ui_add_f32_slider: Box<dyn Fn(String, String, f32, f32, Option<String>) + Send + Sync>,
+    fn ui_add_f32_slider(identifier: str, text: text, min: f32, max: f32, rune_code: opt_str) => UIAddF32Slider, ui_add_f32_slider!;
 
     /// `Sellix::is_paying_for_product(product_id, bearer_tolen)` function. Checks if the user is
     /// paying for the specified Sellix product.
-    sellix_is_paying_for_product: Box<dyn Fn(String, String) -> bool + Send + Sync>,
+    fn sellix_is_paying_for_product(product_id: str, bearer_token: str) -> bool => SellixIsPayingForProduct, sellix_is_paying_for_product!;
 
     /// `Config::has_serial(serial)` function. Checks if the defined serial is present in the
     /// config.
-    config_has_serial: Box<dyn Fn(String) -> bool + Send + Sync>,
+    fn config_has_serial(serial: str) -> bool => ConfigHasSerial, config_has_serial!;
 }
 
 impl DNXFunctions {
-    /// `dynamic::log(message)` function. Logs both to the side-messages, and to `stdout`.
-    pub fn dynamic_log(&self, message: &str) {
-        (self.dynamic_log)(message)
+    /// Validates the function table handed over by the host and copies it into a table of the
+    /// layout this payload was built with.
+    /// Functions past the end of the host's table are left as `None`, so that a payload built
+    /// against a newer template keeps working on an older host.
+    ///
+    /// # Safety
+    /// `functions` has to be null, or point to a table which starts with a `DNXHeader` and is
+    /// at least `header.size` bytes large.
+    pub unsafe fn from_raw(functions: *const Self) -> Result<&'static Self, AbiMismatch> {
+        let Some(header) = functions.cast::<DNXHeader>().as_ref() else {
+            return Err(AbiMismatch::Null);
+        };
+
+        if header.version != ABI_VERSION {
+            return Err(AbiMismatch::Version {
+                host: header.version,
+                payload: ABI_VERSION,
+            });
+        }
+
+        let minimum = std::mem::size_of::<DNXPrefix>() as u32;
+        if header.size < minimum {
+            return Err(AbiMismatch::TooSmall {
+                host: header.size,
+                minimum,
+            });
+        }
+
+        // All-zeroes is a valid table apart from `dynamic_log`, which is always covered by the
+        // copy since the host's table is at least as large as the prefix.
+        // Every field past the header is a pointer, so the copy is rounded down to whole pointers,
+        // rather than leaving one half-copied if the host reports an odd size.
+        let mut table = Box::new(std::mem::MaybeUninit::<Self>::zeroed());
+        let length = (header.size as usize).min(std::mem::size_of::<Self>());
+        let length = length - length % std::mem::size_of::<usize>();
+        std::ptr::copy_nonoverlapping(
+            functions.cast::<u8>(),
+            table.as_mut_ptr().cast::<u8>(),
+            length,
+        );
+
+        Ok(Box::leak(table).assume_init_ref())
     }
 
-    /// `Memory::read_string(address) function. Attempts to read a string at `address`.
-    pub fn memory_read_string(&self, address: i64) -> &'static str {
-        (self.memory_read_string)(address)
+    /// Logs through the host without validating the table first, which is safe to do since the
+    /// header and `dynamic_log` never move.
+    /// Used for reporting why `from_raw` refused the table.
+    ///
+    /// # Safety
+    /// `functions` has to be null, or point to a table which starts with a `DNXHeader`.
+    pub unsafe fn log_raw(functions: *const Self, message: &str) {
+        if let Some(prefix) = functions.cast::<DNXPrefix>().as_ref() {
+            (prefix.dynamic_log)(message.into());
+        }
     }
 
-    /// `PXScript::execute(code, send_to_party, use_main_thread)` function. Executes a script and
-    /// optionally sends it to the party.
-    pub fn pxscript_execute(&self, code: String, send_to_party: bool, use_main_thread: bool) {
-        (self.pxscript_execute)(code, send_to_party, use_main_thread)
+    /// Header of the host's function table.
+    pub fn header(&self) -> DNXHeader {
+        self.header
     }
 
-    /// `dynamic::get_delta_time()` function. Gets the current delta-time of the process.
-    pub fn dynamic_get_delta_time(&self) -> f32 {
-        (self.dynamic_get_delta_time)()
+    /// Checks whether the host provides the function called `name`.
+    /// Unknown names are reported as unsupported.
+    pub fn supports_named(&self, name: &str) -> bool {
+        Capability::from_name(name).is_some_and(|capability| self.supports(capability))
     }
 
-    /// Special function for making dynamic eject the DLL, rather than the other way around.
-    /// This is needed because otherwise the process crashes.
-    pub fn dynamic_eject_payload(
-        &self,
-        process: OwnedProcess,
-        payload: ProcessModule<BorrowedProcess<'static>>,
-    ) {
-        (self.dynamic_eject_payload)(process, payload)
-    }
-
-    /// Rune VM which you may use to execute Rune code.
-    pub fn rune_vm_execute(&self, source: String) {
-        (self.rune_vm_execute)(source);
-    }
-
-    /// `ui::add_window(name)` function. Allocates and displays a new custom window.
-    pub fn ui_add_window(&self, name: String) {
-        (self.ui_add_window)(name);
-    }
-
-    /// `ui::focus_window` function. Focuses the defined window if present.
-    pub fn ui_focus_window(&self, name: String) {
-        (self.ui_focus_window)(name);
+    /// Every capability the host provides.
+    pub fn capabilities(&self) -> impl Iterator<Item = Capability> + '_ {
+        Capability::ALL
+            .iter()
+            .copied()
+            .filter(|capability| self.supports(*capability))
     }
 
-    /// `ui::add_label(identifier, text)` function. Creates a new label with the specified content.
-    pub fn ui_add_label(&self, identifier: String, text: String, font_id: usize) {
-        (self.ui_add_label)(identifier, text, font_id);
-    }
-
-    /// `ui::add_button(identifier, text, code)` function. Creates a new button with the specified
-    /// text and Rune code.
-    pub fn ui_add_button(
-        &self,
-        identifier: String,
-        text: String,
-        source: String,
-        callback: Option<extern "Rust" fn()>,
-    ) {
-        (self.ui_add_button)(identifier, text, source, callback);
-    }
-
-    /// `ui::add_separator(identifier)` function. Adds a new horizontal separator.
-    pub fn ui_add_separator(&self, identifier: String) {
-        (self.ui_add_separator)(identifier);
-    }
-
-    /// `ui::add_spacing(identifier, x, y)` function. Adds spacing between widgets.
-    pub fn ui_add_spacing(&self, identifier: String, x: f32, y: f32) {
-        (self.ui_add_spacing)(identifier, x, y);
-    }
-
-    /// `dynamic::create_thread_key(name)` function. Creates a globally-accessible thread-key.
-    pub fn create_thread_key(&self, identifier: String) {
-        (self.dyamic_add_thread_key)(identifier);
-    }
-
-    /// `dynamic::set_thread_key_value(name, value)` function. Sets the value of a thread-key.
-    pub fn set_thread_key_value(&self, identifier: String, value: bool) {
-        (self.dynamic_set_thread_key_value)(identifier, value)
-    }
-
-    /// `dynamic::get_thread_key(name)` function. Returns the value of the thread-key.
-    pub fn get_thread_key(&self, identifier: String) -> bool {
-        (self.dynamic_get_thread_key)(identifier)
-    }
-
-    /// `ui::get_i32_slider_value(identifier)` function. Returns the i32 value of a defined slider.
-    pub fn get_i32_slider_value(&self, identifier: String) -> i32 {
-        (self.ui_get_i32_slider_value)(identifier)
+    /// `dynamic::log(message)` function. Logs both to the side-messages, and to `stdout`.
+    pub fn dynamic_log(&self, message: &str) {
+        (self.dynamic_log)(message.into())
     }
+}
 
-    /// `ui::get_f32_slider_value(identifier)` function. Returns the f32 value of a defined slider.
-    pub fn get_f32_slider_value(&self, identifier: String) -> f32 {
-        (self.ui_get_f32_slider_value)(identifier)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockHost;
+
+    /// Table the payload copies from a mock host whose table claims to be `size` bytes large.
+    fn copy_with_size(size: usize) -> &'static DNXFunctions {
+        let mut host = MockHost::new();
+        host.set_header(DNXHeader {
+            version: ABI_VERSION,
+            size: size as u32,
+        });
+
+        // SAFETY: The mock's table is complete, so it's at least `size` bytes large.
+        unsafe { DNXFunctions::from_raw(host.table()) }.unwrap()
     }
 
-    /// `ui::set_next_item_same_line(identifier)` function. Attempts to make the next upcoming
-    /// widget on the currently-active line.
-    pub fn ui_set_next_item_same_line(&self, identifier: String) {
-        (self.ui_set_next_item_same_line)(identifier);
+    #[test]
+    fn whole_table_is_copied() {
+        let functions = copy_with_size(std::mem::size_of::<DNXFunctions>());
+        assert_eq!(functions.capabilities().count(), Capability::ALL.len());
     }
 
-    /// `ui::add_custom_font_label(identifier, text, relative_font_path)` function. Adds a new
-    /// label with a custom-loaded font.
-    pub fn ui_add_custom_font_label(
-        &self,
-        identifier: String,
-        text: String,
-        relative_font_path: String,
-    ) {
-        (self.ui_add_custom_font_label)(identifier, text, relative_font_path);
+    #[test]
+    fn functions_past_the_size_are_unsupported() {
+        let prefix = std::mem::size_of::<DNXPrefix>();
+        let functions = copy_with_size(prefix + std::mem::size_of::<usize>());
+        assert!(functions.supports(Capability::MemoryReadString));
+        assert!(!functions.supports(Capability::PXScriptExecute));
     }
 
-    /// `ui::remove_widget(identifier)` function. Attempts to remove the specified widget from the
-    /// focused window.
-    pub fn ui_remove_widget(&self, identifier: String) {
-        (self.ui_remove_widget)(identifier);
-    }
+    #[test]
+    fn partially_covered_functions_are_unsupported() {
+        let prefix = std::mem::size_of::<DNXPrefix>();
+        let functions = copy_with_size(prefix + std::mem::size_of::<usize>() / 2);
+        assert!(functions.supports(Capability::DynamicLog));
+        assert!(!functions.supports(Capability::MemoryReadString));
 
-    /// `ui::remove_all_widgets()` function. Removes all widgets from the focused window.
-    pub fn ui_remove_all_widgets(&self) {
-        (self.ui_remove_all_widgets)();
+        let functions = copy_with_size(prefix + std::mem::size_of::<usize>() * 3 / 2);
+        assert!(functions.supports(Capability::MemoryReadString));
+        assert!(!functions.supports(Capability::PXScriptExecute));
     }
 
-    /// `ui::add_i32_slider(identifier, text, min, max, rune_code)` function. Adds a i32 slider to
-    /// the UI with optional Rune code execution.
-    pub fn ui_add_i32_slider(
-        &self,
-        identifier: String,
-        text: String,
-        min: i32,
-        max: i32,
-        rune_code: Option<String>,
-    ) {
-        (self.ui_add_i32_slider)(identifier, text, min, max, rune_code);
+    #[test]
+    fn mismatched_tables_are_refused() {
+        let mut host = MockHost::new();
+        host.set_header(DNXHeader {
+            version: ABI_VERSION + 1,
+            size: std::mem::size_of::<DNXFunctions>() as u32,
+        });
+        // SAFETY: The mock's table is complete.
+        let error = unsafe { DNXFunctions::from_raw(host.table()) }.err();
+        assert!(matches!(error, Some(AbiMismatch::Version { .. })));
+
+        host.set_header(DNXHeader {
+            version: ABI_VERSION,
+            size: 4,
+        });
+        // SAFETY: As above.
+        let error = unsafe { DNXFunctions::from_raw(host.table()) }.err();
+        assert!(matches!(error, Some(AbiMismatch::TooSmall { .. })));
+
+        // SAFETY: Null is allowed.
+        let error = unsafe { DNXFunctions::from_raw(std::ptr::null()) }.err();
+        assert!(matches!(error, Some(AbiMismatch::Null)));
     }
 
-    /// `ui::add_f32_slider(identifier, text, min, max, rune_code)` function. Adds a f32 slider to
-    /// the UI with optional Rune code execution.
-    pub fn ui_add_f32_slider(
-        &self,
-        identifier: String,
-        text: String,
-        min: f32,
-        max: f32,
-        rune_code: Option<String>,
-    ) {
-        (self.ui_add_f32_slider)(identifier, text, min, max, rune_code);
-    }
+    #[test]
+    fn later_tables_are_refused() {
+        let host = MockHost::new();
+        let installed = host.install().unwrap();
+        let other = copy_table(&host);
+        FUNCTIONS.set(other);
+        assert!(std::ptr::eq(FUNCTIONS.get().unwrap(), installed));
 
-    /// `Sellix::is_paying_for_product(product_id, bearer_tolen)` function. Checks if the user is
-    /// paying for the specified Sellix product.
-    pub fn sellix_is_paying_for_product(&self, product_id: String, bearer_token: String) -> bool {
-        (self.sellix_is_paying_for_product)(product_id, bearer_token)
+        FUNCTIONS.replace_for_test(other);
+        assert!(std::ptr::eq(FUNCTIONS.get().unwrap(), other));
     }
 
-    /// `Config::has_serial(serial)` function. Checks if the defined serial is present in the
-    /// config.
-    pub fn config_has_serial(&self, serial: String) -> bool {
-        (self.config_has_serial)(serial)
+    /// Another copy of the table of `host`.
+    fn copy_table(host: &MockHost) -> &'static DNXFunctions {
+        // SAFETY: The mock's table is complete.
+        unsafe { DNXFunctions::from_raw(host.table()) }.unwrap()
     }
 }
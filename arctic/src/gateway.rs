@@ -0,0 +1,231 @@
+use crate::{
+    abi::*,
+    functions::*,
+    logging, memory,
+    panic::{self, PanicPolicy},
+    payload::MainReturn,
+};
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, Ordering},
+};
+
+/// The running payload, handed to the function marked with `#[arctic::gateway]`.
+/// Every run leaks a new `Gateway`, which only matters for tests running the gateway repeatedly.
+static GATEWAY: AtomicPtr<Gateway> = AtomicPtr::new(std::ptr::null_mut());
+
+/// Handle to the running payload.
+pub struct Gateway {
+    /// Process the payload has been injected into.
+    process: ProcessHandle,
+
+    /// The payload module itself.
+    payload: ModuleHandle,
+
+    /// The validated function table, also available through `FUNCTIONS`.
+    functions: &'static DNXFunctions,
+
+    /// Whether the payload stays loaded after its main function returns.
+    resident: AtomicBool,
+
+    /// Whether something asked for a running `Payload` to be ejected.
+    eject_requested: AtomicBool,
+
+    /// Whether the payload has already asked to be ejected.
+    ejected: AtomicBool,
+
+    /// The `PanicPolicy`, stored as its discriminant.
+    panic_policy: AtomicU8,
+}
+
+impl Gateway {
+    /// Returns the running payload, `None` if the gateway hasn't started yet.
+    pub fn get() -> Option<&'static Self> {
+        // SAFETY: Only ever set to leaked, and thus `'static`, gateways.
+        unsafe { GATEWAY.load(Ordering::Acquire).as_ref() }
+    }
+
+    /// Process the payload has been injected into.
+    pub fn process(&self) -> ProcessHandle {
+        self.process
+    }
+
+    /// The payload module itself.
+    pub fn payload(&self) -> ModuleHandle {
+        self.payload
+    }
+
+    /// Path of the payload library, `None` if it can't be determined.
+    pub fn payload_path(&self) -> Option<PathBuf> {
+        module_path(self.payload)
+    }
+
+    /// The function table of the host.
+    pub fn functions(&self) -> &'static DNXFunctions {
+        self.functions
+    }
+
+    /// Keeps the payload loaded after its main function returns, for payloads which keep running
+    /// through hooks or threads.
+    /// Such payloads have to call `eject` themselves once they're done.
+    pub fn stay_resident(&self) {
+        self.resident.store(true, Ordering::Release);
+    }
+
+    /// Checks whether `stay_resident` has been called.
+    pub fn is_resident(&self) -> bool {
+        self.resident.load(Ordering::Acquire)
+    }
+
+    /// Asks a running `Payload` to wind down, which runs its `on_pre_eject` and `on_unload`
+    /// callbacks on the tick thread before ejecting it.
+    /// One-shot payloads are ejected right away, resident payloads which don't return a `Payload`
+    /// have to check `is_eject_requested` themselves.
+    pub fn request_eject(&self) {
+        self.eject_requested.store(true, Ordering::Release);
+        if !self.is_resident() {
+            self.eject();
+        }
+    }
+
+    /// Checks whether `request_eject` has been called.
+    pub fn is_eject_requested(&self) -> bool {
+        self.eject_requested.load(Ordering::Acquire)
+    }
+
+    /// Decides what happens once a panic has been contained, `PanicPolicy::Eject` by default.
+    pub fn set_panic_policy(&self, policy: PanicPolicy) {
+        self.panic_policy.store(policy as u8, Ordering::Release);
+    }
+
+    /// The current `PanicPolicy`.
+    pub fn panic_policy(&self) -> PanicPolicy {
+        if self.panic_policy.load(Ordering::Acquire) == PanicPolicy::Continue as u8 {
+            PanicPolicy::Continue
+        } else {
+            PanicPolicy::Eject
+        }
+    }
+
+    /// Asks dynamic to eject the payload right away, without running any `Payload` callbacks.
+    /// Every tracked memory write made through `arctic::memory` is undone first.
+    /// Only the first call has any effect, since the payload can't be ejected twice.
+    pub fn eject(&self) {
+        if !self.ejected.swap(true, Ordering::AcqRel) {
+            memory::revert_all();
+            logging::flush();
+            eject_payload!(self.process, self.payload);
+        }
+    }
+}
+
+/// Path of the library `module` was loaded from.
+#[cfg(windows)]
+fn module_path(module: ModuleHandle) -> Option<PathBuf> {
+    use std::{ffi::OsString, os::windows::ffi::OsStringExt};
+    use windows_sys::Win32::System::LibraryLoader::GetModuleFileNameW;
+
+    let mut buffer = vec![0u16; 32_768];
+    // SAFETY: The buffer is as large as the size passed along.
+    let length =
+        unsafe { GetModuleFileNameW(module.0, buffer.as_mut_ptr(), buffer.len() as u32) } as usize;
+
+    (length != 0 && length < buffer.len()).then(|| OsString::from_wide(&buffer[..length]).into())
+}
+
+/// Path of the library the payload was loaded from.
+/// Module handles can't be turned into paths on Unix, so this looks up the library containing this
+/// very function instead.
+#[cfg(unix)]
+fn module_path(_module: ModuleHandle) -> Option<PathBuf> {
+    use std::{ffi::CStr, os::unix::ffi::OsStrExt};
+
+    let mut info = std::mem::MaybeUninit::<libc::Dl_info>::zeroed();
+    // SAFETY: `dladdr` only fills in `info`, and the address is that of a function.
+    if unsafe { libc::dladdr(module_path as *const libc::c_void, info.as_mut_ptr()) } == 0 {
+        return None;
+    }
+
+    // SAFETY: `dladdr` succeeded, so `info` has been filled in and `dli_fname` is null or a valid
+    // string owned by the dynamic linker.
+    let info = unsafe { info.assume_init() };
+    if info.dli_fname.is_null() {
+        return None;
+    }
+
+    // SAFETY: Checked for null above.
+    let path = unsafe { CStr::from_ptr(info.dli_fname) };
+    Some(std::ffi::OsStr::from_bytes(path.to_bytes()).into())
+}
+
+/// Validates the host's function table, stores it in `FUNCTIONS` and creates the `Gateway`.
+/// Returns `None` after logging why if the table has been refused.
+///
+/// # Safety
+/// `functions` has to point to a table which starts with a `DNXHeader`, and which lives until the
+/// payload has been ejected.
+pub(crate) unsafe fn start(
+    process: ProcessHandle,
+    payload: ModuleHandle,
+    functions: *const DNXFunctions,
+) -> Option<&'static Gateway> {
+    let functions = match DNXFunctions::from_raw(functions) {
+        Ok(functions) => functions,
+        Err(error) => {
+            // The header and `dynamic_log` are frozen across all ABI versions.
+            DNXFunctions::log_raw(
+                functions,
+                &format!("[ERROR] Arctic Gateway refused to start, {error}!"),
+            );
+            return None;
+        }
+    };
+
+    // Store the functions structure globally.
+    FUNCTIONS.set(functions);
+    logging::install();
+    panic::install_hook();
+
+    let gateway = Box::leak(Box::new(Gateway {
+        process,
+        payload,
+        functions,
+        resident: AtomicBool::new(false),
+        eject_requested: AtomicBool::new(false),
+        ejected: AtomicBool::new(false),
+        panic_policy: AtomicU8::new(PanicPolicy::Eject as u8),
+    }));
+    GATEWAY.store(gateway, Ordering::Release);
+    Some(gateway)
+}
+
+/// Runs the payload's main function, called by the `arctic_gateway` function which
+/// `#[arctic::gateway]` generates.
+///
+/// # Safety
+/// `functions` has to point to a table which starts with a `DNXHeader`, and which lives until the
+/// payload has been ejected.
+#[doc(hidden)]
+pub unsafe fn run<R: MainReturn>(
+    process: ProcessHandle,
+    payload: ModuleHandle,
+    functions: *const DNXFunctions,
+    main: fn(&Gateway) -> R,
+) {
+    let Some(gateway) = start(process, payload, functions) else {
+        return;
+    };
+
+    // Unwinding into the host would take the whole process down with it.
+    match panic::catch_panic(|| main(gateway)) {
+        Ok(result) => result.start(gateway),
+        Err(message) => {
+            panic::handle(gateway, "main", &message);
+
+            // Nothing is left running, unless main asked to stay resident and may keep running.
+            if !gateway.is_resident() || gateway.panic_policy() == PanicPolicy::Eject {
+                gateway.eject();
+            }
+        }
+    }
+}
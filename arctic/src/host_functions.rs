@@ -0,0 +1,284 @@
+/// Generates everything a host function needs from a single line in `functions.rs`:
+/// - The field in `DNXFunctions`.
+/// - The forwarding method on `DNXFunctions`, which returns `Unsupported` if the host doesn't
+///   provide the function.
+/// - The `Capability` variant, along with its name.
+/// - The `#[macro_export]` macro, which silently does nothing and returns the default value if the
+///   gateway isn't ready, the host doesn't provide the function or an argument is invalid.
+/// - The fallible function in `arctic::api`, which reports every failure as a `GatewayError`.
+///
+/// Every line looks like `fn field(param: type, ...) -> type => Capability, macro_name!;`, where
+/// the return type is optional. Types are single tokens, `str` and `text` are passed as `&str`,
+/// `opt_str` as `Option<&str>` and `address` as a `memory::Address`, every other type is passed
+/// as-is.
+/// `str` is for names, such as widget ids, which the fallible API refuses if they're empty or
+/// contain a null byte, while `text` and `opt_str` may be empty, but not contain null bytes either.
+/// The macros accept anything implementing `HostStr` for `str` and `text`, such as `format_args!`,
+/// anything dereferencing to `str` inside an `Option` for `opt_str`, such as `Option<String>`, and
+/// anything convertible into an `Address` for `address`, such as pointers.
+/// A bare `None` has to be given a type for `opt_str`, as in `None::<&str>`.
+/// The doc comments of a line are used for all of the generated items.
+macro_rules! define_host_functions {
+    ($(
+        $(#[doc = $doc:expr])*
+        fn $name:ident($($param:ident: $ty:tt),*) $(-> $ret:tt)? => $capability:ident, $macro:ident!;
+    )*) => {
+        /// A structure that contains a set of functions from dynamic.
+        /// The layout is `#[repr(C)]` and every function uses the C ABI, so that the host and the
+        /// payload don't need to be built by the same compiler. Strings are passed as borrowed
+        /// `FfiStr`s, which are only valid for the duration of the call.
+        /// Every function except `dynamic_log` is optional, functions the host doesn't know about
+        /// or doesn't implement are `None` and report `Unsupported` when called.
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        pub struct DNXFunctions {
+            /// Version and size of the table, used to check that the host and the payload agree on
+            /// the layout.
+            pub(crate) header: $crate::abi::DNXHeader,
+
+            /// `dynamic::log(message)` function. Logs both to the side-messages, and to `stdout`.
+            pub(crate) dynamic_log: extern "C" fn($crate::abi::FfiStr),
+
+            $(
+                $(#[doc = $doc])*
+                pub(crate) $name: Option<extern "C" fn($(ffi_type!($ty)),*) $(-> ffi_type!($ret))?>,
+            )*
+        }
+
+        /// A single host function which may or may not be provided by the host.
+        /// Hosts built against an older template simply don't know about newer functions, so
+        /// payloads can ask for them through `DNXFunctions::supports` before relying on them.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum Capability {
+            /// `dynamic::log(message)` function. Logs both to the side-messages, and to `stdout`.
+            DynamicLog,
+
+            $(
+                $(#[doc = $doc])*
+                $capability,
+            )*
+        }
+
+        impl Capability {
+            /// Every known capability, in the order they appear in the function table.
+            pub const ALL: &'static [Self] = &[Self::DynamicLog, $(Self::$capability),*];
+
+            /// Name of the host function, as written in the function table.
+            pub const fn name(self) -> &'static str {
+                match self {
+                    Self::DynamicLog => "dynamic_log",
+                    $(Self::$capability => stringify!($name),)*
+                }
+            }
+        }
+
+        impl DNXFunctions {
+            /// Function table which only provides `dynamic_log`, for hosts which fill in the rest
+            /// one by one.
+            pub const fn unsupported(dynamic_log: extern "C" fn($crate::abi::FfiStr)) -> Self {
+                Self {
+                    header: $crate::abi::DNXHeader::current::<Self>(),
+                    dynamic_log,
+                    $($name: None,)*
+                }
+            }
+
+            /// Checks whether the host provides the given function.
+            pub fn supports(&self, capability: Capability) -> bool {
+                match capability {
+                    Capability::DynamicLog => true,
+                    $(Capability::$capability => self.$name.is_some(),)*
+                }
+            }
+
+            $(
+                $(#[doc = $doc])*
+                pub fn $name(
+                    &self,
+                    $($param: arg_type!($ty)),*
+                ) -> Result<return_type!($($ret)?), $crate::capabilities::Unsupported> {
+                    self.$name
+                        .ok_or($crate::capabilities::Unsupported(Capability::$capability))
+                        .map(|function| from_ffi!([$($ret)?], function($(to_ffi!($ty, $param)),*)))
+                }
+            )*
+        }
+
+        /// Fallible versions of the host function macros, which tell apart real values from the
+        /// gateway not being ready or the host not supporting a function.
+        pub mod api {
+            use super::*;
+            use $crate::error::GatewayError;
+
+            /// Returns the function table, or `NotInitialized` if the gateway hasn't started yet.
+            pub fn functions() -> Result<&'static DNXFunctions, GatewayError> {
+                FUNCTIONS.get().ok_or(GatewayError::NotInitialized)
+            }
+
+            $(
+                $(#[doc = $doc])*
+                #[doc = ""]
+                #[doc = concat!("Fallible version of `", stringify!($macro), "!`.")]
+                pub fn $name($($param: arg_type!($ty)),*) -> Result<return_type!($($ret)?), GatewayError> {
+                    let functions = functions()?;
+                    $(validate_arg!($ty, $param)?;)*
+                    let function = functions
+                        .$name
+                        .ok_or(GatewayError::Unsupported(Capability::$capability))?;
+                    try_from_ffi!([$($ret)?], $name, function($(to_ffi!($ty, $param)),*))
+                }
+            )*
+        }
+
+        $(
+            $(#[doc = $doc])*
+            #[macro_export]
+            macro_rules! $macro {
+                ($($$ $param:expr),*) => {
+                    $crate::api::$name($($crate::__host_function_arg!($ty, $$ $param)),*)
+                        .unwrap_or_default()
+                };
+            }
+        )*
+    };
+}
+
+/// Type a host function parameter or return value has in the function table.
+macro_rules! ffi_type {
+    (str) => {
+        $crate::abi::FfiStr
+    };
+    (text) => {
+        $crate::abi::FfiStr
+    };
+    (opt_str) => {
+        $crate::abi::FfiStr
+    };
+    (address) => {
+        i64
+    };
+    ($ty:tt) => {
+        $ty
+    };
+}
+
+/// Type a host function parameter has in the forwarding method.
+macro_rules! arg_type {
+    (str) => {
+        &str
+    };
+    (text) => {
+        &str
+    };
+    (opt_str) => {
+        Option<&str>
+    };
+    (address) => {
+        $crate::memory::Address
+    };
+    ($ty:tt) => {
+        $ty
+    };
+}
+
+/// Type a host function returns from the forwarding method.
+macro_rules! return_type {
+    () => {
+        ()
+    };
+    (str) => {
+        String
+    };
+    ($ty:tt) => {
+        $ty
+    };
+}
+
+/// Converts a forwarding method parameter into the type the host expects.
+macro_rules! to_ffi {
+    (str, $value:expr) => {
+        $crate::abi::FfiStr::new($value)
+    };
+    (text, $value:expr) => {
+        $crate::abi::FfiStr::new($value)
+    };
+    (opt_str, $value:expr) => {
+        $crate::abi::FfiStr::from_option($value)
+    };
+    (address, $value:expr) => {
+        $value.get() as i64
+    };
+    ($ty:tt, $value:expr) => {
+        $value
+    };
+}
+
+/// Converts a value returned by the host into the type the forwarding method returns.
+macro_rules! from_ffi {
+    ([str], $value:expr) => {
+        // SAFETY: Strings returned by the host are owned by the host, and copied before anything
+        // else is called. Invalid strings are returned as empty strings.
+        unsafe { $value.as_str() }
+            .map(str::to_owned)
+            .unwrap_or_default()
+    };
+    ([$($ty:tt)?], $value:expr) => {
+        $value
+    };
+}
+
+/// Converts a value returned by the host into the type the fallible API returns, reporting
+/// strings the host failed to produce as `GatewayError::Host`.
+macro_rules! try_from_ffi {
+    ([str], $name:ident, $value:expr) => {
+        // SAFETY: Strings returned by the host are owned by the host, and copied before anything
+        // else is called.
+        unsafe { $value.as_str() }
+            .map(str::to_owned)
+            .ok_or_else(|| {
+                $crate::error::GatewayError::Host(
+                    concat!("`", stringify!($name), "` returned no valid string").to_owned(),
+                )
+            })
+    };
+    ([$($ty:tt)?], $name:ident, $value:expr) => {
+        Ok($value)
+    };
+}
+
+/// Validates string parameters before they're handed to the host, see `define_host_functions!`.
+macro_rules! validate_arg {
+    (str, $value:expr) => {
+        $crate::error::validate_identifier($value)
+    };
+    (text, $value:expr) => {
+        $crate::error::validate_text($value)
+    };
+    (opt_str, $value:expr) => {
+        $value.map_or(Ok(()), $crate::error::validate_text)
+    };
+    ($ty:tt, $value:expr) => {
+        Ok::<(), $crate::error::GatewayError>(())
+    };
+}
+
+/// Converts a macro argument into the type the forwarding method expects.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __host_function_arg {
+    (str, $value:expr) => {
+        &*$crate::abi::HostStr::host_str(&$value)
+    };
+    (text, $value:expr) => {
+        &*$crate::abi::HostStr::host_str(&$value)
+    };
+    (opt_str, $value:expr) => {
+        ($value).as_deref()
+    };
+    (address, $value:expr) => {
+        $crate::memory::Address::from($value)
+    };
+    ($ty:tt, $value:expr) => {
+        $value
+    };
+}
@@ -0,0 +1,503 @@
+use crate::{
+    abi::*,
+    functions::DNXFunctions,
+    gateway::{self, Gateway},
+    memory,
+    panic::{catch_panic, contain},
+    payload::{self, MainReturn, Payload},
+};
+use std::{
+    ffi::c_void,
+    sync::{Mutex, MutexGuard, PoisonError},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "hot-reload")]
+pub use shim::HotReload;
+
+/// Receives the state saved by the old version of a payload, see `unload`.
+pub type StateSink = extern "C" fn(context: *mut c_void, state: FfiBytes);
+
+/// Payload running under a hot-reload shim, driven through the `arctic_hot_*` functions which
+/// `#[arctic::gateway(hot_reload)]` exports next to `arctic_gateway`.
+static HOSTED: Mutex<Option<Box<dyn Payload>>> = Mutex::new(None);
+
+/// Threads spawned through `spawn`, which have to exit before the payload's library is unloaded.
+static THREADS: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+/// How long `unload` waits for the threads spawned through `spawn` to exit.
+const THREAD_EXIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Locks `HOSTED`, every callback runs with panics contained so it's never poisoned in practice.
+fn hosted() -> MutexGuard<'static, Option<Box<dyn Payload>>> {
+    HOSTED.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Locks `THREADS`.
+fn threads() -> MutexGuard<'static, Vec<JoinHandle<()>>> {
+    THREADS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Spawns a thread which a hot-reload shim waits for before unloading the payload, since unmapping
+/// code a thread is still running would take the process down.
+/// The thread has to exit once `on_unload` has been called, the old version is kept loaded if it's
+/// still running a second later. Threads spawned any other way aren't tracked.
+pub fn spawn(f: impl FnOnce() + Send + 'static) {
+    let mut threads = threads();
+    join_finished(&mut threads);
+    threads.push(std::thread::spawn(f));
+}
+
+/// Joins the threads in `threads` which have finished, since a finished thread may still be on its
+/// way out, and returns how many are still running.
+fn join_finished(threads: &mut Vec<JoinHandle<()>>) -> usize {
+    let (finished, running) = std::mem::take(threads)
+        .into_iter()
+        .partition::<Vec<_>, _>(JoinHandle::is_finished);
+
+    // Panics have been reported by the panic hook already.
+    for thread in finished {
+        let _ = thread.join();
+    }
+
+    *threads = running;
+    threads.len()
+}
+
+/// Waits up to `THREAD_EXIT_TIMEOUT` for the threads spawned through `spawn` to exit, returning how
+/// many are still running.
+fn join_threads() -> usize {
+    let start = Instant::now();
+    let mut threads = threads();
+    while threads.iter().any(|thread| !thread.is_finished())
+        && start.elapsed() < THREAD_EXIT_TIMEOUT
+    {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    join_finished(&mut threads)
+}
+
+/// Starts the payload under a hot-reload shim, restoring `state` if it has been reloaded.
+/// Unlike `gateway::run`, the payload is never ejected by itself, it's ticked and unloaded by the
+/// shim instead.
+/// Returns `false` if nothing is running, in which case the shim may unload the library right away.
+///
+/// # Safety
+/// Same as `gateway::run`, and `state` has to be null or valid for the duration of the call.
+#[doc(hidden)]
+pub unsafe fn boot<R: MainReturn>(
+    process: ProcessHandle,
+    payload: ModuleHandle,
+    functions: *const DNXFunctions,
+    state: FfiBytes,
+    main: fn(&Gateway) -> R,
+) -> bool {
+    let Some(gateway) = gateway::start(process, payload, functions) else {
+        return false;
+    };
+
+    // The shim decides when the payload is ejected.
+    gateway.stay_resident();
+    let payload = match catch_panic(|| main(gateway).into_payload()) {
+        Ok(payload) => payload,
+        Err(message) => {
+            tracing::error!("Payload panicked, unloading it! Message: {message}");
+            return false;
+        }
+    };
+
+    // One-shot payloads simply run again on every reload.
+    let Some(mut payload) = payload else {
+        return true;
+    };
+
+    if let Some(state) = state.as_slice() {
+        contain(gateway, "restore_state", || payload.restore_state(state));
+    }
+
+    payload::load(&mut *payload, gateway);
+    *hosted() = Some(payload);
+    true
+}
+
+/// Ticks the payload under a hot-reload shim.
+/// Returns `false` once the payload has requested ejection.
+#[doc(hidden)]
+pub fn tick(delta_time: f32) -> bool {
+    let Some(gateway) = Gateway::get() else {
+        return false;
+    };
+
+    match hosted().as_mut() {
+        Some(payload) => payload::tick(&mut **payload, gateway, delta_time),
+        None => !gateway.is_eject_requested(),
+    }
+}
+
+/// Winds the payload under a hot-reload shim down, handing the state it saved to `sink` along with
+/// `context` before calling `on_unload`, and waits for the threads spawned through `spawn`.
+/// Tracked memory writes are undone as well, the next version makes its own.
+/// Returns how many of those threads are still running, the library may only be unloaded if none
+/// are.
+#[doc(hidden)]
+pub fn unload(context: *mut c_void, sink: StateSink) -> usize {
+    if let (Some(gateway), Some(mut payload)) = (Gateway::get(), hosted().take()) {
+        contain(gateway, "on_pre_eject", || payload.on_pre_eject(gateway));
+        let state = catch_panic(|| payload.save_state()).unwrap_or_else(|message| {
+            tracing::error!(
+                "Payload panicked in save_state, dropping its state! Message: {message}"
+            );
+            Vec::new()
+        });
+
+        sink(context, state.as_slice().into());
+        contain(gateway, "on_unload", || payload.on_unload());
+    }
+
+    let running = join_threads();
+    memory::revert_all();
+    running
+}
+
+/// The shim side, which loads the actual payload and reloads it whenever it's rebuilt.
+#[cfg(feature = "hot-reload")]
+mod shim {
+    use super::*;
+    use libloading::Library;
+    use std::{
+        env::consts::{DLL_PREFIX, DLL_SUFFIX},
+        fs,
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            OnceLock,
+        },
+        time::SystemTime,
+    };
+
+    /// How often the library is checked for changes.
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Set once the hosted payload asks to be ejected, which only the shim may do.
+    static EJECT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+    type Boot =
+        unsafe extern "C" fn(ProcessHandle, ModuleHandle, *const DNXFunctions, FfiBytes) -> bool;
+    type Tick = extern "C" fn(f32) -> bool;
+    type Unload = extern "C" fn(*mut c_void, StateSink) -> usize;
+
+    /// Stands in for `dynamic_eject_payload` in the table handed to the hosted payload, since
+    /// ejecting the shim from under it would take the process down.
+    extern "C" fn request_eject(_process: ProcessHandle, _payload: ModuleHandle) {
+        EJECT_REQUESTED.store(true, Ordering::Release);
+    }
+
+    /// Stores the state handed over by `unload` in the `Vec` pointed to by `context`.
+    extern "C" fn receive_state(context: *mut c_void, state: FfiBytes) {
+        // SAFETY: `context` is the `Vec` passed by `HotReload::unload_current`, and `state` is
+        // valid for the duration of the call.
+        unsafe {
+            *context.cast::<Vec<u8>>() = state.as_slice().unwrap_or_default().to_vec();
+        }
+    }
+
+    /// The host's function table, with ejection redirected to the shim.
+    /// The header describes the shim's layout rather than the host's, since that's what's leaked
+    /// here. Functions the shim doesn't know about are left out, even if the host provides them.
+    fn functions(gateway: &Gateway) -> &'static DNXFunctions {
+        static FUNCTIONS: OnceLock<&'static DNXFunctions> = OnceLock::new();
+        FUNCTIONS.get_or_init(|| {
+            Box::leak(Box::new(DNXFunctions {
+                header: DNXHeader::current::<DNXFunctions>(),
+                dynamic_eject_payload: Some(request_eject),
+                ..*gateway.functions()
+            }))
+        })
+    }
+
+    /// Modification time of `path`, `None` if it doesn't exist.
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    /// A loaded version of the payload.
+    struct Version {
+        library: Library,
+
+        /// Copy of the library which has been loaded, removed once it's unloaded.
+        path: PathBuf,
+
+        tick: Tick,
+        unload: Unload,
+    }
+
+    /// A thin payload which loads the actual payload from a library, and reloads it whenever the
+    /// library is rebuilt without ejecting from the host.
+    /// Every version is loaded from its own copy, so that the library can be rebuilt while it's
+    /// loaded. The old version is unloaded first, and the state it saved through
+    /// `Payload::save_state` is handed to the new version through `Payload::restore_state`.
+    /// Versions which fail to load are logged, the shim then waits for the next build.
+    pub struct HotReload {
+        /// Library the actual payload is built into.
+        library: PathBuf,
+
+        /// Thread-key which ejects the shim once set to `true`.
+        eject_thread_key: Option<&'static str>,
+
+        /// The running version, if any.
+        current: Option<Version>,
+
+        /// Number of versions loaded so far, used for naming the copies.
+        versions: u32,
+
+        /// Modification time of the library when it was last loaded.
+        loaded: Option<SystemTime>,
+
+        /// Modification time seen by the last poll, which is reloaded once it stops changing.
+        pending: Option<SystemTime>,
+
+        /// When the library was last checked for changes.
+        last_poll: Instant,
+
+        /// State saved by the last version, until a new version takes it over.
+        state: Option<Vec<u8>>,
+    }
+
+    impl HotReload {
+        /// Creates a shim for the payload built into `library`.
+        pub fn new(library: impl Into<PathBuf>) -> Self {
+            Self {
+                library: library.into(),
+                eject_thread_key: None,
+                current: None,
+                versions: 0,
+                loaded: None,
+                pending: None,
+                last_poll: Instant::now(),
+                state: None,
+            }
+        }
+
+        /// Ejects the shim once the thread-key called `identifier` is set to `true`.
+        pub fn with_eject_thread_key(mut self, identifier: &'static str) -> Self {
+            self.eject_thread_key = Some(identifier);
+            self
+        }
+
+        /// Library `cargo build` produces for this template, unless overridden through the
+        /// `ARCTIC_HOT_RELOAD_LIBRARY` environment variable.
+        /// The shim itself has to be built into another target directory, for example through
+        /// `cargo build --features hot-reload --target-dir target/hot-reload`.
+        pub fn default_library() -> PathBuf {
+            if let Some(library) = std::env::var_os("ARCTIC_HOT_RELOAD_LIBRARY") {
+                return library.into();
+            }
+
+            let profile = if cfg!(debug_assertions) {
+                "debug"
+            } else {
+                "release"
+            };
+
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("target")
+                .join(profile)
+                .join(format!(
+                    "{DLL_PREFIX}{}{DLL_SUFFIX}",
+                    env!("CARGO_CRATE_NAME")
+                ))
+        }
+
+        /// Loads the current build of the library, logging why if it fails.
+        fn load(&mut self, gateway: &Gateway) {
+            self.loaded = modified(&self.library);
+            self.versions += 1;
+            match self.load_version(gateway) {
+                Ok(version) => {
+                    self.current = Some(version);
+                    self.state = None;
+                    tracing::info!(
+                        "Hot reload loaded version {} of {}.",
+                        self.versions,
+                        self.library.display()
+                    );
+                }
+                Err(error) => tracing::error!(
+                    "Hot reload failed loading {}, {error}! Waiting for the next build.",
+                    self.library.display()
+                ),
+            }
+        }
+
+        /// Copies the library, loads the copy and boots it.
+        fn load_version(&self, gateway: &Gateway) -> Result<Version, String> {
+            let directory = std::env::temp_dir().join("arctic_hot_reload");
+            fs::create_dir_all(&directory).map_err(|error| error.to_string())?;
+
+            let file_name = self
+                .library
+                .file_name()
+                .ok_or("the path has no file name")?;
+            let path = directory.join(format!(
+                "{}-{}-{}",
+                std::process::id(),
+                self.versions,
+                file_name.to_string_lossy()
+            ));
+            fs::copy(&self.library, &path).map_err(|error| error.to_string())?;
+
+            // SAFETY: The library is a payload built from this template, and `functions` is
+            // leaked so it outlives every version.
+            let version = unsafe { Self::boot(gateway, &path, self.state.as_deref()) };
+            if version.is_err() {
+                let _ = fs::remove_file(&path);
+            }
+
+            version
+        }
+
+        /// Loads the library at `path` and boots it, handing `state` over.
+        ///
+        /// # Safety
+        /// The library has to be a payload built from this template.
+        unsafe fn boot(
+            gateway: &Gateway,
+            path: &Path,
+            state: Option<&[u8]>,
+        ) -> Result<Version, String> {
+            let library = Library::new(path).map_err(|error| error.to_string())?;
+            let symbol_error = |error: libloading::Error| {
+                format!("{error}, is its main function marked with #[arctic::gateway(hot_reload)]?")
+            };
+            let boot = *library
+                .get::<Boot>(b"arctic_hot_boot\0")
+                .map_err(symbol_error)?;
+            let tick = *library
+                .get::<Tick>(b"arctic_hot_tick\0")
+                .map_err(symbol_error)?;
+            let unload = *library
+                .get::<Unload>(b"arctic_hot_unload\0")
+                .map_err(symbol_error)?;
+
+            if !boot(
+                gateway.process(),
+                gateway.payload(),
+                functions(gateway),
+                FfiBytes::from_option(state),
+            ) {
+                return Err("its main function failed".to_owned());
+            }
+
+            Ok(Version {
+                library,
+                path: path.to_owned(),
+                tick,
+                unload,
+            })
+        }
+
+        /// Unloads the running version, keeping the state it saved for the next one.
+        fn unload_current(&mut self) {
+            let Some(version) = self.current.take() else {
+                return;
+            };
+
+            let mut state = Vec::new();
+            let running = (version.unload)(std::ptr::from_mut(&mut state).cast(), receive_state);
+            self.state = Some(state);
+
+            // Unmapping code which threads spawned by the version are still running would take
+            // the process down, so the library stays loaded unless they have all exited.
+            if running != 0 {
+                tracing::warn!(
+                    "Hot reload kept the old version loaded, since {running} threads it spawned \
+                    are still running. Make them exit in on_unload!"
+                );
+                std::mem::forget(version.library);
+            } else if let Err(error) = version.library.close() {
+                tracing::error!("Hot reload failed unloading the old version, error: {error}");
+            }
+
+            let _ = fs::remove_file(&version.path);
+        }
+
+        /// Reloads the library once it has changed and stopped changing, so that half-written
+        /// builds aren't loaded.
+        fn poll(&mut self, gateway: &Gateway) {
+            let modified = modified(&self.library);
+            if modified.is_none() || modified == self.loaded {
+                self.pending = None;
+                return;
+            }
+
+            if modified != self.pending {
+                self.pending = modified;
+                return;
+            }
+
+            self.pending = None;
+            tracing::info!("Hot reload detected a new build, reloading...");
+            self.unload_current();
+            self.load(gateway);
+        }
+    }
+
+    impl Payload for HotReload {
+        fn on_load(&mut self, gateway: &Gateway) {
+            self.load(gateway);
+        }
+
+        fn on_tick(&mut self, gateway: &Gateway, delta_time: f32) {
+            if self.last_poll.elapsed() >= POLL_INTERVAL {
+                self.last_poll = Instant::now();
+                self.poll(gateway);
+            }
+
+            let running = self
+                .current
+                .as_ref()
+                .is_none_or(|version| (version.tick)(delta_time));
+
+            if !running || EJECT_REQUESTED.load(Ordering::Acquire) {
+                gateway.request_eject();
+            }
+        }
+
+        fn on_pre_eject(&mut self, _gateway: &Gateway) {
+            self.unload_current();
+        }
+
+        fn eject_thread_key(&self) -> Option<&str> {
+            self.eject_thread_key
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    #[test]
+    fn spawned_threads_are_waited_for() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        spawn(move || {
+            while !thread_stop.load(Ordering::Acquire) {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        });
+        assert_eq!(join_threads(), 1);
+
+        stop.store(true, Ordering::Release);
+        assert_eq!(join_threads(), 0);
+        assert!(threads().is_empty());
+    }
+}
@@ -1,29 +1,66 @@
-use crate::functions::*;
-use dll_syringe::process::{BorrowedProcess, OwnedProcess, ProcessModule};
-use std::sync::Arc;
+// Needed by `define_host_functions!` for generating the `#[macro_export]` macros.
+#![feature(macro_metavar_expr)]
 
-mod functions;
+use crate::gateway::Gateway;
+
+// Lets `#[arctic::gateway]` refer to this crate as `::arctic`, both in here and in payloads which
+// depend on it.
+extern crate self as arctic;
+
+pub use arctic_macros::gateway;
+
+// Re-exported so that payloads log through the same versions the crate forwards to dynamic.
+pub use tracing;
+pub use tracing_log::log;
+
+#[macro_use]
+mod host_functions;
+
+pub mod abi;
+pub mod capabilities;
+pub mod error;
+
+#[macro_use]
+pub mod functions;
 
 #[macro_use]
 mod macros;
 
-/// This is the Arctic Gateway main function.
-/// Enable no_mangle so that the function name doesn't get mangled. This is only required for the
-/// main function, not for the rest.
-/// The purpose of the gateway is to collect a set of useful functions, then store them globally.
-/// Note that you **need** to have all of the initial variables defined, otherwise you'll crash.
-#[no_mangle]
-pub fn arctic_gateway(
-    process: OwnedProcess,
-    payload: ProcessModule<BorrowedProcess<'static>>,
-    functions: Arc<DNXFunctions>,
-) {
-    // Store the functions structure globally.
-    FUNCTIONS.get_or_init(|| functions);
+pub mod gateway;
+pub mod hot_reload;
+pub mod logging;
+pub mod memory;
+pub mod modules;
+pub mod panic;
+pub mod payload;
+pub mod scanner;
+
+pub use functions::api;
 
+#[cfg(any(test, feature = "mock-host"))]
+pub mod mock;
+
+/// This is the payload's main function.
+/// `#[arctic::gateway]` exports it as `arctic_gateway`, which checks that the host's function
+/// table matches the layout this payload was built with and stores it globally before calling it.
+/// Hosts which are older than the payload are fine, functions they don't provide report
+/// `Unsupported` instead, see `DNXFunctions::supports`.
+/// The payload is ejected once this returns, call `ctx.stay_resident()` to keep it loaded, or
+/// return a `Payload` to have the gateway drive it until it's ejected.
+/// `hot_reload` lets the shim built with the `hot-reload` feature load it, and can be dropped for
+/// builds which are only ever injected directly.
+#[cfg(not(feature = "hot-reload"))]
+#[arctic::gateway(hot_reload)]
+fn main(_ctx: &Gateway) {
     // Try and log to dynamic.
     log!("Arctic Gateway template active!");
+}
 
-    // Eject.
-    eject_payload!(process, payload);
+/// With `hot-reload` enabled, the payload is only a shim which loads the template built without
+/// it, and reloads it whenever it's rebuilt.
+#[cfg(feature = "hot-reload")]
+#[arctic::gateway]
+fn main(_ctx: &Gateway) -> hot_reload::HotReload {
+    hot_reload::HotReload::new(hot_reload::HotReload::default_library())
+        .with_eject_thread_key("arctic_hot_reload_eject")
 }
@@ -0,0 +1,562 @@
+use crate::{functions::FUNCTIONS, gateway::Gateway};
+use std::{
+    fmt::{Debug, Display, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Mutex, MutexGuard, Once, PoisonError, RwLock, TryLockError},
+    time::{Duration, Instant},
+};
+use tracing::{
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+    span, Event, Level, Metadata, Subscriber,
+};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::{
+    layer::{Context, SubscriberExt},
+    registry::LookupSpan,
+    Layer, Registry,
+};
+
+mod file;
+mod throttle;
+
+pub use file::FileSink;
+#[doc(hidden)]
+pub use throttle::RateLimit;
+use throttle::Throttle;
+
+/// How often the thread-key bound through `bind_thread_key` is checked.
+const THREAD_KEY_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Maximum level per target, parsed from comma-separated directives such as
+/// `warn,my_payload=debug,arctic_template::modules=trace`.
+/// A directive without a target sets the level of every other target, and targets match
+/// themselves along with every module below them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogFilter {
+    /// Level of targets without a directive of their own.
+    default: LevelFilter,
+
+    /// Levels of specific targets, longest target first.
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    /// Filter which lets records up to `level` through, from every target.
+    pub const fn new(level: LevelFilter) -> Self {
+        Self {
+            default: level,
+            targets: Vec::new(),
+        }
+    }
+
+    /// Overrides the level of `target`, and every module below it.
+    pub fn with_target(mut self, target: impl Into<String>, level: LevelFilter) -> Self {
+        let target = target.into();
+        self.targets.retain(|(existing, _)| *existing != target);
+        self.targets.push((target, level));
+        self.targets
+            .sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        self
+    }
+
+    /// Maximum level of records from `target`.
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .find(|(prefix, _)| {
+                target
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// Checks whether a record described by `metadata` passes the filter.
+    pub fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= self.level_for(metadata.target())
+    }
+}
+
+/// Logs `INFO` and more severe records from every target.
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self::new(LevelFilter::INFO)
+    }
+}
+
+impl FromStr for LogFilter {
+    type Err = InvalidFilter;
+
+    fn from_str(directives: &str) -> Result<Self, Self::Err> {
+        let mut filter = Self::default();
+        for directive in directives.split(',').map(str::trim) {
+            if directive.is_empty() {
+                continue;
+            }
+
+            let invalid = || InvalidFilter(directive.to_owned());
+
+            // `LevelFilter` parses an empty string as `ERROR`.
+            let level = |level: &str| match level.trim() {
+                "" => Err(invalid()),
+                level => level.parse::<LevelFilter>().map_err(|_| invalid()),
+            };
+
+            match directive.split_once('=') {
+                Some((target, level_name)) if !target.trim().is_empty() => {
+                    filter = filter.with_target(target.trim(), level(level_name)?);
+                }
+                Some(_) => return Err(invalid()),
+                None => filter.default = level(directive)?,
+            }
+        }
+
+        Ok(filter)
+    }
+}
+
+/// A directive of a `LogFilter` which couldn't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidFilter(pub String);
+
+impl Display for InvalidFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid log filter directive {:?}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidFilter {}
+
+/// Filters applied to every record, see `set_filter` and `bind_thread_key`.
+struct Filters {
+    /// Filter set through `set_filter`.
+    configured: LogFilter,
+
+    /// Thread-key which replaces the configured filter while it's `true`, and its filter.
+    thread_key: Option<(String, LogFilter)>,
+
+    /// Whether the thread-key was `true` when it was last checked.
+    overridden: bool,
+
+    /// When the thread-key was last checked.
+    last_poll: Option<Instant>,
+}
+
+impl Filters {
+    /// The filter currently in effect.
+    fn active(&self) -> &LogFilter {
+        match &self.thread_key {
+            Some((_, filter)) if self.overridden => filter,
+            _ => &self.configured,
+        }
+    }
+
+    /// Checks whether the thread-key should be polled again.
+    fn poll_due(&self) -> bool {
+        self.thread_key.is_some()
+            && self
+                .last_poll
+                .is_none_or(|last_poll| last_poll.elapsed() >= THREAD_KEY_POLL_INTERVAL)
+    }
+}
+
+static FILTERS: RwLock<Filters> = RwLock::new(Filters {
+    configured: LogFilter::new(LevelFilter::INFO),
+    thread_key: None,
+    overridden: false,
+    last_poll: None,
+});
+
+/// Replaces the filter applied to every record, `info` by default.
+pub fn set_filter(filter: LogFilter) {
+    FILTERS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .configured = filter;
+}
+
+/// The filter set through `set_filter`.
+pub fn filter() -> LogFilter {
+    FILTERS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .configured
+        .clone()
+}
+
+/// Reads filter directives from the file at `path` and applies them, see `LogFilter`.
+pub fn load_filter(path: impl AsRef<Path>) -> std::io::Result<()> {
+    let filter = std::fs::read_to_string(path)?
+        .parse()
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+
+    set_filter(filter);
+    Ok(())
+}
+
+/// Applies `filter` instead of the configured one while the thread-key called `identifier` is
+/// `true`, for example for turning on debug logging from the host at runtime.
+/// The thread-key is created and reset to `false`, and checked at most every 250ms while logging.
+pub fn bind_thread_key(identifier: &str, filter: LogFilter) {
+    create_thread_key!(identifier);
+    set_thread_key_value!(identifier, false);
+
+    let mut filters = FILTERS.write().unwrap_or_else(PoisonError::into_inner);
+    filters.thread_key = Some((identifier.to_owned(), filter));
+    filters.overridden = false;
+    filters.last_poll = None;
+}
+
+/// Checks whether a record described by `metadata` passes the filter currently in effect.
+fn enabled(metadata: &Metadata<'_>) -> bool {
+    let filters = FILTERS.read().unwrap_or_else(PoisonError::into_inner);
+    if !filters.poll_due() {
+        return filters.active().enabled(metadata);
+    }
+
+    drop(filters);
+    let identifier = {
+        let mut filters = FILTERS.write().unwrap_or_else(PoisonError::into_inner);
+        if !filters.poll_due() {
+            return filters.active().enabled(metadata);
+        }
+
+        // Claims the poll, so that records logged while the host is asked don't ask it again.
+        filters.last_poll = Some(Instant::now());
+        filters
+            .thread_key
+            .as_ref()
+            .map(|(identifier, _)| identifier.clone())
+    };
+
+    // The host is asked without holding the lock, since it may well log back right away.
+    let overridden = identifier
+        .as_deref()
+        .is_some_and(|identifier| get_thread_key!(identifier));
+
+    let mut filters = FILTERS.write().unwrap_or_else(PoisonError::into_inner);
+    if filters
+        .thread_key
+        .as_ref()
+        .map(|(identifier, _)| identifier)
+        == identifier.as_ref()
+    {
+        filters.overridden = overridden;
+    }
+
+    filters.active().enabled(metadata)
+}
+
+static THROTTLE: Mutex<Throttle> = Mutex::new(Throttle::new());
+
+/// Sends `line` to `dynamic_log`, and to the file sink if it's enabled.
+fn forward(level: Option<Level>, line: &str) {
+    if let Some(functions) = FUNCTIONS.get() {
+        functions.dynamic_log(line);
+    }
+
+    file::write(level, line);
+}
+
+/// Locks `mutex`, which the panic hook logs through as well.
+/// While panicking, `None` if the mutex is held, since it may be held by this very thread, which
+/// would deadlock rather than log the panic.
+fn lock<T>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    if !std::thread::panicking() {
+        return Some(mutex.lock().unwrap_or_else(PoisonError::into_inner));
+    }
+
+    match mutex.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+/// Forwards `line` unless it repeats the previous message or exceeds the rate limit.
+/// Lines logged while panicking bypass the throttle if it's held.
+fn emit(level: Option<Level>, line: &str) {
+    match lock(&THROTTLE) {
+        Some(mut throttle) => throttle.process(level, line, Instant::now(), &mut forward),
+        None => forward(level, line),
+    }
+}
+
+/// Forgets repeated and dropped messages along with the history, so that every `MockHost` starts
+/// out with a fresh throttle.
+#[cfg(any(test, feature = "mock-host"))]
+pub(crate) fn reset_throttle() {
+    *THROTTLE.lock().unwrap_or_else(PoisonError::into_inner) = Throttle::new();
+}
+
+/// Logs `message` without a level, used by `log!`.
+#[doc(hidden)]
+pub fn raw(message: &str) {
+    emit(None, message);
+}
+
+/// Additionally logs every record, timestamped, to the file described by `sink`, which survives
+/// the game closing or crashing.
+/// Warnings and errors are flushed right away, everything else at the latest once the payload is
+/// ejected or panics. Returns the path of the log file.
+pub fn enable_file_sink(sink: FileSink) -> std::io::Result<PathBuf> {
+    let directory = Gateway::get()
+        .and_then(Gateway::payload_path)
+        .and_then(|path| path.parent().map(Path::to_owned))
+        .unwrap_or_default();
+
+    file::open(sink, directory)
+}
+
+/// Stops logging to the file sink, flushing what's left.
+pub fn disable_file_sink() {
+    file::close();
+}
+
+/// Limits how many messages are logged per second, `None` lifting the limit. Warnings and errors
+/// are never dropped, and how many messages were dropped is logged along with the next one let
+/// through. Defaults to 30.
+/// Repeats of the same message are collapsed into `… repeated N×` regardless.
+pub fn set_rate_limit(max_per_second: Option<u32>) {
+    THROTTLE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .set_max_per_second(max_per_second);
+}
+
+/// Sets how many of the most recently logged messages are kept for `recent`, 256 by default.
+pub fn set_history_capacity(capacity: usize) {
+    THROTTLE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .set_history_capacity(capacity);
+}
+
+/// The most recently logged messages, oldest first, as they were sent to `dynamic_log`.
+pub fn recent() -> Vec<String> {
+    THROTTLE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .history()
+}
+
+/// Logs what the throttle has held back so far and flushes the file sink, if it's enabled.
+pub(crate) fn flush() {
+    if let Some(mut throttle) = lock(&THROTTLE) {
+        throttle.summarize(&mut forward);
+    }
+
+    file::flush();
+}
+
+/// Fields of a span, formatted as `name=value` pairs.
+struct SpanFields(String);
+
+/// Formats the fields of an event or span, keeping the message apart.
+#[derive(Default)]
+struct FieldFormatter {
+    message: String,
+    fields: String,
+}
+
+impl Visit for FieldFormatter {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        match field.name() {
+            "message" => self.message = format!("{value:?}"),
+            // Metadata of records forwarded from `log`, already part of the normalized metadata.
+            name if name.starts_with("log.") => {}
+            name => {
+                if !self.fields.is_empty() {
+                    self.fields.push(' ');
+                }
+
+                let _ = write!(self.fields, "{name}={value:?}");
+            }
+        }
+    }
+}
+
+/// Forwards events to `dynamic_log` as `[LEVEL] span{fields}:span: target: message fields`.
+struct DynamicLogLayer;
+
+impl<S> Layer<S> for DynamicLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn register_callsite(
+        &self,
+        _metadata: &'static Metadata<'static>,
+    ) -> tracing::subscriber::Interest {
+        // The filter can change at any time, so it can't be cached per callsite.
+        tracing::subscriber::Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        metadata.is_span() || enabled(metadata)
+    }
+
+    fn on_new_span(&self, attributes: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let mut formatter = FieldFormatter::default();
+        attributes.record(&mut formatter);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanFields(formatter.fields));
+        }
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut formatter = FieldFormatter::default();
+        values.record(&mut formatter);
+        let mut extensions = span.extensions_mut();
+        if let Some(fields) = extensions.get_mut::<SpanFields>() {
+            if !fields.0.is_empty() && !formatter.fields.is_empty() {
+                fields.0.push(' ');
+            }
+
+            fields.0.push_str(&formatter.fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        // Records forwarded from `log` all share the same metadata, the actual one is normalized
+        // from their fields.
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+        if !enabled(metadata) {
+            return;
+        }
+
+        let mut line = format!("[{}] ", metadata.level());
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                line.push_str(span.name());
+                if let Some(SpanFields(fields)) = span.extensions().get::<SpanFields>() {
+                    if !fields.is_empty() {
+                        let _ = write!(line, "{{{fields}}}");
+                    }
+                }
+
+                line.push(':');
+            }
+
+            line.push(' ');
+        }
+
+        let mut formatter = FieldFormatter::default();
+        event.record(&mut formatter);
+        let _ = write!(line, "{}: {}", metadata.target(), formatter.message);
+        if !formatter.fields.is_empty() {
+            let _ = write!(line, " {}", formatter.fields);
+        }
+
+        emit(Some(*metadata.level()), &line);
+    }
+}
+
+/// Routes `tracing` events and `log` records, including those of third-party crates, to
+/// `dynamic_log`.
+/// Payloads which install their own subscriber or logger beforehand keep theirs.
+pub(crate) fn install() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let _ = tracing::subscriber::set_global_default(Registry::default().with(DynamicLogLayer));
+        let _ = tracing_log::LogTracer::init();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockHost;
+
+    fn filter(directives: &str) -> LogFilter {
+        directives.parse().unwrap()
+    }
+
+    #[test]
+    fn directives_set_levels() {
+        assert_eq!(filter(""), LogFilter::default());
+        assert_eq!(filter("warn"), LogFilter::new(LevelFilter::WARN));
+        assert_eq!(filter(" TRACE , "), LogFilter::new(LevelFilter::TRACE));
+        assert_eq!(filter("off").level_for("anything"), LevelFilter::OFF);
+
+        let filter = filter("error, my_payload=debug, my_payload::hooks = trace");
+        assert_eq!(filter.level_for("other"), LevelFilter::ERROR);
+        assert_eq!(filter.level_for("my_payload"), LevelFilter::DEBUG);
+        assert_eq!(filter.level_for("my_payload::ui"), LevelFilter::DEBUG);
+        assert_eq!(filter.level_for("my_payload::hooks"), LevelFilter::TRACE);
+        assert_eq!(
+            filter.level_for("my_payload::hooks::detour"),
+            LevelFilter::TRACE
+        );
+        assert_eq!(filter.level_for("my_payload_extra"), LevelFilter::ERROR);
+    }
+
+    #[test]
+    fn later_directives_win() {
+        let filter = filter("info,my_payload=debug,warn,my_payload=error");
+        assert_eq!(filter.level_for("other"), LevelFilter::WARN);
+        assert_eq!(filter.level_for("my_payload"), LevelFilter::ERROR);
+    }
+
+    #[test]
+    fn malformed_directives_are_refused() {
+        let invalid = |directive: &str| Err(InvalidFilter(directive.to_owned()));
+        assert_eq!("loud".parse::<LogFilter>(), invalid("loud"));
+        assert_eq!("info,=debug".parse::<LogFilter>(), invalid("=debug"));
+        assert_eq!("my_payload=".parse::<LogFilter>(), invalid("my_payload="));
+        assert_eq!(
+            "my_payload=debug=trace".parse::<LogFilter>(),
+            invalid("my_payload=debug=trace")
+        );
+    }
+
+    #[test]
+    fn panics_while_logging_are_still_logged() {
+        let host = MockHost::new();
+        host.install().unwrap();
+        install();
+        crate::panic::install_hook();
+
+        let result = std::panic::catch_unwind(|| {
+            let _throttle = THROTTLE.lock().unwrap_or_else(PoisonError::into_inner);
+            panic!("while logging");
+        });
+
+        assert!(result.is_err());
+        assert!(host
+            .logs()
+            .iter()
+            .any(|line| line.contains("while logging")));
+    }
+
+    #[test]
+    fn thread_key_overrides_the_filter() {
+        let host = MockHost::new();
+        host.install().unwrap();
+        install();
+        set_filter(LogFilter::default());
+        bind_thread_key("debug_logging", filter("debug"));
+        assert_eq!(host.thread_key("debug_logging"), Some(false));
+
+        tracing::debug!("hidden");
+        host.set_thread_key("debug_logging", true);
+        std::thread::sleep(THREAD_KEY_POLL_INTERVAL);
+        tracing::debug!("shown");
+
+        FILTERS
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .thread_key = None;
+        let logs = host.logs();
+        assert!(!logs.iter().any(|line| line.contains("hidden")));
+        assert!(logs
+            .iter()
+            .any(|line| line.starts_with("[DEBUG]") && line.ends_with("shown")));
+    }
+}
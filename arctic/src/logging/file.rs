@@ -0,0 +1,320 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::Level;
+
+/// Where the file sink logs to and when it rotates, see `logging::enable_file_sink`.
+#[derive(Clone, Debug)]
+pub struct FileSink {
+    /// Directory of the log files, the payload's directory if `None`.
+    directory: Option<PathBuf>,
+
+    /// Name of the log files without the `.log` extension.
+    file_name: String,
+
+    /// Size in bytes after which the log file is rotated.
+    max_size: u64,
+
+    /// How many rotated log files are kept next to the current one.
+    max_files: usize,
+}
+
+impl FileSink {
+    /// Logs to `arctic.log` in the payload's directory, rotated once it reaches 5 MiB, keeping the
+    /// last 3 rotated files.
+    pub fn new() -> Self {
+        Self {
+            directory: None,
+            file_name: "arctic".to_owned(),
+            max_size: 5 * 1024 * 1024,
+            max_files: 3,
+        }
+    }
+
+    /// Logs into `directory` instead of the payload's directory.
+    pub fn with_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    /// Names the log files `<file_name>.log`, `<file_name>.1.log` and so on.
+    pub fn with_file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = file_name.into();
+        self
+    }
+
+    /// Rotates the log file once it reaches `max_size` bytes.
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Keeps the last `max_files` rotated log files, older ones are deleted.
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+
+    /// Path of the log file rotated `index` times, the current one being `0`.
+    fn path(&self, directory: &Path, index: usize) -> PathBuf {
+        match index {
+            0 => directory.join(format!("{}.log", self.file_name)),
+            index => directory.join(format!("{}.{index}.log", self.file_name)),
+        }
+    }
+}
+
+impl Default for FileSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The log file currently written to.
+struct OpenFile {
+    sink: FileSink,
+    directory: PathBuf,
+    writer: BufWriter<File>,
+
+    /// Size of the log file, including what's still buffered.
+    size: u64,
+}
+
+impl OpenFile {
+    /// Opens the current log file of `sink` in `directory`, appending to it.
+    fn open(sink: FileSink, directory: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&directory)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(sink.path(&directory, 0))?;
+
+        Ok(Self {
+            size: file.metadata()?.len(),
+            writer: BufWriter::new(file),
+            sink,
+            directory,
+        })
+    }
+
+    /// Moves every log file one index up, deleting the oldest, and starts a new one.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        let sink = &self.sink;
+        let _ = fs::remove_file(sink.path(&self.directory, sink.max_files));
+        for index in (0..sink.max_files).rev() {
+            let _ = fs::rename(
+                sink.path(&self.directory, index),
+                sink.path(&self.directory, index + 1),
+            );
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(sink.path(&self.directory, 0))?;
+
+        self.writer = BufWriter::new(file);
+        self.size = 0;
+        Ok(())
+    }
+
+    /// Writes a single timestamped line, flushing right away for warnings and errors since those
+    /// are the most likely to precede a crash.
+    fn write(&mut self, level: Option<Level>, line: &str) -> io::Result<()> {
+        let line = format!("{} {line}\n", timestamp(SystemTime::now()));
+        if self.size > 0 && self.size + line.len() as u64 > self.sink.max_size {
+            self.rotate()?;
+        }
+
+        self.writer.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        if level.is_some_and(|level| level <= Level::WARN) {
+            self.writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+static FILE: Mutex<Option<OpenFile>> = Mutex::new(None);
+
+/// Locks `FILE`, which is also used from the panic hook so it mustn't give up on poisoning, and
+/// mustn't wait for it while panicking, see `logging::lock`.
+fn file() -> Option<MutexGuard<'static, Option<OpenFile>>> {
+    super::lock(&FILE)
+}
+
+/// Starts logging to the file described by `sink`, in `directory` unless the sink has its own.
+/// Returns the path of the log file.
+pub(crate) fn open(sink: FileSink, directory: PathBuf) -> io::Result<PathBuf> {
+    let directory = sink.directory.clone().unwrap_or(directory);
+    let path = sink.path(&directory, 0);
+    let open = OpenFile::open(sink, directory)?;
+    if let Some(mut previous) = file().and_then(|mut file| file.replace(open)) {
+        let _ = previous.writer.flush();
+    }
+
+    Ok(path)
+}
+
+/// Stops logging to the file, flushing what's left.
+pub(crate) fn close() {
+    if let Some(mut open) = file().and_then(|mut file| file.take()) {
+        let _ = open.writer.flush();
+    }
+}
+
+/// Writes `line` to the log file, if there's one.
+pub(crate) fn write(level: Option<Level>, line: &str) {
+    if let Some(open) = file().as_mut().and_then(|file| file.as_mut()) {
+        // There's nowhere left to report failing to log.
+        let _ = open.write(level, line);
+    }
+}
+
+/// Flushes the log file, if there's one.
+pub(crate) fn flush() {
+    if let Some(open) = file().as_mut().and_then(|file| file.as_mut()) {
+        let _ = open.writer.flush();
+    }
+}
+
+/// Formats `time` as a UTC timestamp, such as `2024-01-31 23:59:59.999Z`.
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let (days, seconds) = (
+        since_epoch.as_secs() / 86_400,
+        since_epoch.as_secs() % 86_400,
+    );
+
+    // Days since the epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}.{:03}Z",
+        seconds / 3_600,
+        seconds / 60 % 60,
+        seconds % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Empty directory for a single test.
+    fn directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("arctic_file_sink_{}_{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn lines(path: &Path) -> Vec<String> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            // Drops the timestamp.
+            .map(|line| line.split_once("Z ").unwrap().1.to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn timestamps_are_utc() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01 00:00:00.000Z");
+        assert_eq!(
+            timestamp(UNIX_EPOCH + Duration::from_millis(1_709_210_096_789)),
+            "2024-02-29 12:34:56.789Z"
+        );
+        assert_eq!(
+            timestamp(UNIX_EPOCH + Duration::from_secs(951_868_799)),
+            "2000-02-29 23:59:59.000Z"
+        );
+    }
+
+    #[test]
+    fn files_are_rotated_at_the_size_limit() {
+        let directory = directory("rotation");
+        // Every line is 25 bytes for the timestamp and separator, along with the message.
+        let sink = FileSink::new()
+            .with_file_name("test")
+            .with_max_size(70)
+            .with_max_files(2);
+        let mut file = OpenFile::open(sink.clone(), directory.clone()).unwrap();
+        for line in ["one", "two", "three", "four", "five"] {
+            file.write(None, line).unwrap();
+        }
+
+        file.writer.flush().unwrap();
+        assert_eq!(lines(&sink.path(&directory, 0)), ["five"]);
+        assert_eq!(lines(&sink.path(&directory, 1)), ["three", "four"]);
+        assert_eq!(lines(&sink.path(&directory, 2)), ["one", "two"]);
+
+        file.write(None, "six").unwrap();
+        file.write(None, "seven").unwrap();
+        file.writer.flush().unwrap();
+        assert_eq!(lines(&sink.path(&directory, 0)), ["seven"]);
+        assert_eq!(lines(&sink.path(&directory, 1)), ["five", "six"]);
+        assert_eq!(lines(&sink.path(&directory, 2)), ["three", "four"]);
+        assert!(!sink.path(&directory, 3).exists());
+        let _ = fs::remove_dir_all(directory);
+    }
+
+    #[test]
+    fn existing_files_are_appended_to() {
+        let directory = directory("append");
+        let sink = FileSink::new().with_file_name("test");
+        let mut file = OpenFile::open(sink.clone(), directory.clone()).unwrap();
+        file.write(Some(Level::WARN), "first").unwrap();
+        drop(file);
+
+        let mut file = OpenFile::open(sink.clone(), directory.clone()).unwrap();
+        file.write(Some(Level::ERROR), "second").unwrap();
+        assert_eq!(lines(&sink.path(&directory, 0)), ["first", "second"]);
+        let _ = fs::remove_dir_all(directory);
+    }
+
+    #[test]
+    fn panics_while_the_file_is_locked_dont_deadlock() {
+        /// Logs while the panic unwinds past it, with `FILE` still locked.
+        struct LogOnUnwind;
+
+        impl Drop for LogOnUnwind {
+            fn drop(&mut self) {
+                write(Some(Level::ERROR), "unwinding");
+                flush();
+            }
+        }
+
+        // Keeps the panic's message from reaching another test's mock.
+        let _host = crate::mock::MockHost::new();
+        let result = std::panic::catch_unwind(|| {
+            let _file = file();
+            let _log = LogOnUnwind;
+            panic!("while writing");
+        });
+
+        assert!(result.is_err());
+    }
+}
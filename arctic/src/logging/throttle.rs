@@ -0,0 +1,409 @@
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::Level;
+
+/// How often a message which keeps repeating reports how often it repeated.
+const REPEAT_SUMMARY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Window over which the rate limit is counted.
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(1);
+
+/// How many of the most recent distinct messages are checked for repeats, so that messages logged
+/// in turns, such as by two per-frame hooks, are collapsed as well.
+const TRACKED_MESSAGES: usize = 8;
+
+/// A message which has been let through, along with how often it has been repeated since.
+struct Tracked {
+    line: String,
+    repeats: u64,
+
+    /// When the repeats started being counted.
+    since: Instant,
+}
+
+/// Collapses repeated messages and rate-limits the rest before they reach dynamic, so that per-frame
+/// code paths can't flood the side-messages, and remembers the most recent messages.
+pub(crate) struct Throttle {
+    /// Most recent distinct messages let through, oldest first.
+    tracked: VecDeque<Tracked>,
+
+    /// Maximum messages let through per second, apart from warnings and errors.
+    max_per_second: Option<u32>,
+
+    /// Start of the current rate limit window, and how many messages it let through or dropped.
+    window_start: Option<Instant>,
+    admitted: u32,
+    dropped: u64,
+
+    /// Most recent messages let through, oldest first.
+    history: VecDeque<String>,
+    history_capacity: usize,
+}
+
+impl Throttle {
+    pub(crate) const fn new() -> Self {
+        Self {
+            tracked: VecDeque::new(),
+            max_per_second: Some(30),
+            window_start: None,
+            admitted: 0,
+            dropped: 0,
+            history: VecDeque::new(),
+            history_capacity: 256,
+        }
+    }
+
+    /// Passes `line` on to `emit` unless it repeats a recent message or exceeds the rate limit,
+    /// along with summaries of what has been held back.
+    pub(crate) fn process(
+        &mut self,
+        level: Option<Level>,
+        line: &str,
+        now: Instant,
+        emit: &mut impl FnMut(Option<Level>, &str),
+    ) {
+        let repeated = match self.tracked.iter_mut().find(|tracked| tracked.line == line) {
+            Some(tracked) => {
+                if tracked.repeats == 0 {
+                    tracked.since = now;
+                }
+
+                tracked.repeats += 1;
+                true
+            }
+            None => false,
+        };
+
+        self.summarize_repeats(now, false, emit);
+        if repeated {
+            return;
+        }
+
+        // Warnings and errors are never dropped.
+        let important = level.is_some_and(|level| level <= Level::WARN);
+        if !important && !self.admit(now) {
+            self.dropped += 1;
+            return;
+        }
+
+        // Only messages which have been let through are tracked, so that repeats of dropped ones
+        // are never summarized.
+        if self.tracked.len() >= TRACKED_MESSAGES {
+            if let Some(oldest) = self.tracked.pop_front() {
+                self.summarize_repeated(&oldest, emit);
+            }
+        }
+
+        self.tracked.push_back(Tracked {
+            line: line.to_owned(),
+            repeats: 0,
+            since: now,
+        });
+
+        self.summarize_dropped(emit);
+        self.emit(level, line, emit);
+    }
+
+    /// Reports everything which has been held back so far, used before flushing.
+    pub(crate) fn summarize(&mut self, emit: &mut impl FnMut(Option<Level>, &str)) {
+        self.summarize_repeats(Instant::now(), true, emit);
+        self.summarize_dropped(emit);
+    }
+
+    /// Reports how often tracked messages repeated since they were last reported, once they have
+    /// been repeating for `REPEAT_SUMMARY_INTERVAL` unless `all` is set.
+    fn summarize_repeats(
+        &mut self,
+        now: Instant,
+        all: bool,
+        emit: &mut impl FnMut(Option<Level>, &str),
+    ) {
+        let mut summaries = Vec::new();
+        for tracked in &mut self.tracked {
+            if tracked.repeats > 0
+                && (all || now.duration_since(tracked.since) >= REPEAT_SUMMARY_INTERVAL)
+            {
+                summaries.push(repeat_summary(tracked));
+                tracked.repeats = 0;
+            }
+        }
+
+        for summary in summaries {
+            self.emit(None, &summary, emit);
+        }
+    }
+
+    /// Reports how often `tracked` repeated, if it did.
+    fn summarize_repeated(
+        &mut self,
+        tracked: &Tracked,
+        emit: &mut impl FnMut(Option<Level>, &str),
+    ) {
+        if tracked.repeats > 0 {
+            self.emit(None, &repeat_summary(tracked), emit);
+        }
+    }
+
+    /// Reports how many messages the rate limit dropped.
+    fn summarize_dropped(&mut self, emit: &mut impl FnMut(Option<Level>, &str)) {
+        if self.dropped > 0 {
+            let summary = format!("… {} messages dropped by the rate limit", self.dropped);
+            self.dropped = 0;
+            self.emit(None, &summary, emit);
+        }
+    }
+
+    /// Counts a message against the rate limit, returning `false` if it has to be dropped.
+    fn admit(&mut self, now: Instant) -> bool {
+        let Some(max_per_second) = self.max_per_second else {
+            return true;
+        };
+
+        if self
+            .window_start
+            .is_none_or(|start| now.duration_since(start) >= RATE_LIMIT_WINDOW)
+        {
+            self.window_start = Some(now);
+            self.admitted = 0;
+        }
+
+        self.admitted += 1;
+        self.admitted <= max_per_second
+    }
+
+    /// Lets `line` through, remembering it.
+    fn emit(
+        &mut self,
+        level: Option<Level>,
+        line: &str,
+        emit: &mut impl FnMut(Option<Level>, &str),
+    ) {
+        if self.history_capacity > 0 {
+            if self.history.len() >= self.history_capacity {
+                self.history.pop_front();
+            }
+
+            self.history.push_back(line.to_owned());
+        }
+
+        emit(level, line);
+    }
+
+    pub(crate) fn set_max_per_second(&mut self, max_per_second: Option<u32>) {
+        self.max_per_second = max_per_second;
+    }
+
+    pub(crate) fn set_history_capacity(&mut self, capacity: usize) {
+        self.history_capacity = capacity;
+        while self.history.len() > capacity {
+            self.history.pop_front();
+        }
+    }
+
+    pub(crate) fn history(&self) -> Vec<String> {
+        self.history.iter().cloned().collect()
+    }
+}
+
+/// Summary of how often `tracked` repeated, such as `… repeated 240×: Hook reached`.
+fn repeat_summary(tracked: &Tracked) -> String {
+    format!("… repeated {}×: {}", tracked.repeats, tracked.line)
+}
+
+/// Lets a call site through at most once per interval, see `log_every!`.
+#[doc(hidden)]
+pub struct RateLimit(Mutex<Option<Instant>>);
+
+impl RateLimit {
+    pub const fn new() -> Self {
+        Self(Mutex::new(None))
+    }
+
+    /// Checks whether at least `interval` has passed since this last returned `true`.
+    pub fn ready(&self, interval: Duration) -> bool {
+        let mut last = self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        let now = Instant::now();
+        if last.is_some_and(|last| now.duration_since(last) < interval) {
+            return false;
+        }
+
+        *last = Some(now);
+        true
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockHost;
+
+    /// Lines a throttle lets through.
+    struct Output(Vec<String>);
+
+    impl Output {
+        fn process(
+            &mut self,
+            throttle: &mut Throttle,
+            level: Option<Level>,
+            line: &str,
+            now: Instant,
+        ) {
+            throttle.process(level, line, now, &mut |_, line: &str| {
+                self.0.push(line.to_owned())
+            });
+        }
+
+        fn take(&mut self) -> Vec<String> {
+            std::mem::take(&mut self.0)
+        }
+    }
+
+    #[test]
+    fn repeats_are_summarized() {
+        let (mut throttle, mut output, start) =
+            (Throttle::new(), Output(Vec::new()), Instant::now());
+        for frame in 0..10 {
+            output.process(
+                &mut throttle,
+                None,
+                "tick",
+                start + Duration::from_millis(frame),
+            );
+        }
+
+        assert_eq!(output.take(), ["tick"]);
+        let later = start + REPEAT_SUMMARY_INTERVAL * 2;
+        output.process(&mut throttle, None, "tick", later);
+        assert_eq!(output.take(), ["… repeated 10×: tick"]);
+
+        output.process(&mut throttle, None, "tick", later);
+        throttle.summarize(&mut |_, line| output.0.push(line.to_owned()));
+        assert_eq!(output.take(), ["… repeated 1×: tick"]);
+    }
+
+    #[test]
+    fn alternating_messages_are_collapsed() {
+        let (mut throttle, mut output, start) =
+            (Throttle::new(), Output(Vec::new()), Instant::now());
+        for frame in 0..100 {
+            let line = if frame % 2 == 0 {
+                "first hook"
+            } else {
+                "second hook"
+            };
+            output.process(
+                &mut throttle,
+                None,
+                line,
+                start + Duration::from_millis(frame),
+            );
+        }
+
+        assert_eq!(output.take(), ["first hook", "second hook"]);
+        output.process(
+            &mut throttle,
+            None,
+            "other",
+            start + REPEAT_SUMMARY_INTERVAL * 2,
+        );
+        assert_eq!(
+            output.take(),
+            [
+                "… repeated 49×: first hook",
+                "… repeated 49×: second hook",
+                "other"
+            ]
+        );
+    }
+
+    #[test]
+    fn evicted_messages_are_summarized() {
+        let (mut throttle, mut output, now) = (Throttle::new(), Output(Vec::new()), Instant::now());
+        output.process(&mut throttle, None, "first", now);
+        output.process(&mut throttle, None, "first", now);
+        for index in 1..TRACKED_MESSAGES {
+            output.process(&mut throttle, None, &index.to_string(), now);
+        }
+
+        output.take();
+        output.process(&mut throttle, None, "last", now);
+        assert_eq!(output.take(), ["… repeated 1×: first", "last"]);
+        output.process(&mut throttle, None, "first", now);
+        assert_eq!(output.take(), ["first"]);
+    }
+
+    #[test]
+    fn rate_limit_drops_the_excess() {
+        let (mut throttle, mut output, start) =
+            (Throttle::new(), Output(Vec::new()), Instant::now());
+        throttle.set_max_per_second(Some(2));
+        output.process(&mut throttle, None, "one", start);
+        output.process(&mut throttle, None, "two", start);
+        output.process(&mut throttle, None, "three", start);
+        output.process(&mut throttle, Some(Level::WARN), "warning", start);
+        assert_eq!(
+            output.take(),
+            [
+                "one",
+                "two",
+                "… 1 messages dropped by the rate limit",
+                "warning"
+            ]
+        );
+
+        // Dropped messages aren't counted as repeats once they're let through.
+        output.process(&mut throttle, None, "three", start + RATE_LIMIT_WINDOW);
+        assert_eq!(output.take(), ["three"]);
+        throttle.summarize(&mut |_, line| output.0.push(line.to_owned()));
+        assert!(output.take().is_empty());
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let (mut throttle, mut output, now) = (Throttle::new(), Output(Vec::new()), Instant::now());
+        throttle.set_max_per_second(None);
+        throttle.set_history_capacity(3);
+        for index in 0..5 {
+            output.process(&mut throttle, None, &index.to_string(), now);
+        }
+
+        assert_eq!(throttle.history(), ["2", "3", "4"]);
+        throttle.set_history_capacity(1);
+        assert_eq!(throttle.history(), ["4"]);
+        throttle.set_history_capacity(0);
+        output.process(&mut throttle, None, "5", now);
+        assert!(throttle.history().is_empty());
+        assert_eq!(output.take().len(), 6);
+    }
+
+    #[test]
+    fn call_sites_are_limited() {
+        let host = MockHost::new();
+        host.install().unwrap();
+        for index in 0..3 {
+            log_once!("once {index}");
+            log_every!(Duration::from_secs(60), "every {index}");
+        }
+
+        assert_eq!(host.logs(), ["once 0", "every 0"]);
+
+        let limit = RateLimit::new();
+        assert!(limit.ready(Duration::from_millis(20)));
+        assert!(!limit.ready(Duration::from_millis(20)));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(limit.ready(Duration::from_millis(20)));
+    }
+}
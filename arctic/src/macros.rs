@@ -1,146 +1,149 @@
 /// `dynamic::log(message)` function. Logs both to the side-messages, and to `stdout`.
+/// This bypasses levels and filters, `tracing` and `log` records are forwarded to the same place,
+/// see `arctic::logging`.
+/// Takes either a single message, or a format string and its arguments like `format!`.
 #[macro_export]
 macro_rules! log {
-    ($message:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.dynamic_log($message);
-        }
-    };
-}
-
-/// `PXScript::execute(code, send_to_party, use_main_thread)` function. Executes a script and
-/// optionally sends it to the party.
-#[macro_export]
-macro_rules! pxscript_execute {
-    ($code:expr, $send_to_party:expr, $use_main_thread:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.pxscript_execute($code.to_owned(), $send_to_party, $use_main_thread);
-        }
-    };
-}
-
-/// Special function for making dynamic eject the DLL, rather than the other way around.
-/// This is needed because otherwise the process crashes.
-#[macro_export]
-macro_rules! eject_payload {
-    ($process:expr, $payload:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.dynamic_eject_payload($process, $payload);
-        }
-    };
-}
-
-/// Rune VM which you may use to execute Rune code.
-#[macro_export]
-macro_rules! rune_vm_execute {
-    ($source:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.rune_vm_execute($source);
-        }
+    ($message:literal) => {
+        $crate::logging::raw(&*$crate::abi::HostStr::host_str(&::std::format_args!($message)))
     };
-}
+    ($message:expr) => {
+        $crate::logging::raw(&*$crate::abi::HostStr::host_str(&$message))
+    };
+    ($($arg:tt)+) => {
+        $crate::logging::raw(&*$crate::abi::HostStr::host_str(&::std::format_args!($($arg)+)))
+    };
+}
+
+/// Like `log!`, but only logs the first time this call site is reached, for code which runs every
+/// frame such as hooks.
+#[macro_export]
+macro_rules! log_once {
+    ($($message:tt)+) => {{
+        static ONCE: ::std::sync::Once = ::std::sync::Once::new();
+        ONCE.call_once(|| $crate::log!($($message)+));
+    }};
+}
+
+/// Like `log!`, but logs at most once every `$interval`, a `Duration`, from this call site, for
+/// code which runs every frame such as hooks.
+#[macro_export]
+macro_rules! log_every {
+    ($interval:expr, $($message:tt)+) => {{
+        static LIMIT: $crate::logging::RateLimit = $crate::logging::RateLimit::new();
+        if LIMIT.ready($interval) {
+            $crate::log!($($message)+);
+        }
+    }};
+}
+
+/// Declares a handle to a struct in the game's memory, whose fields are read and written at fixed
+/// offsets rather than through hand-written pointer arithmetic.
+/// Every field gets a method returning a `memory::Field`, whose `get` and `set` read and write it.
+/// Fields whose type was declared with `remote_struct!` are pointers to that struct, and `String`
+/// fields are read-only null-terminated strings, see `memory::read_c_string`.
+/// `dump` logs every field through `log!`, which helps with figuring out a layout.
+/// ```ignore
+/// remote_struct! {
+///     /// Player controlled by the local client.
+///     pub struct Player {
+///         0x1C8 => health: f32,
+///         0x30 => position: [f32; 3],
+///         0x40 => weapon: Weapon,
+///         0x50 => name: String,
+///     }
+/// }
+///
+/// player.health().set(100.0)?;
+/// let ammo = player.weapon().get()?.ammo().get()?;
+/// player.dump();
+/// ```
+#[macro_export]
+macro_rules! remote_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $offset:literal => $field:ident: $ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+        $vis struct $name($crate::memory::Address);
+
+        // Layouts tend to declare more fields than the payload ends up using.
+        #[allow(dead_code)]
+        impl $name {
+            /// Handle to the struct at `address`.
+            pub const fn new(address: $crate::memory::Address) -> Self {
+                Self(address)
+            }
 
-/// `ui::add_window(name)` function. Allocates and displays a new custom window.
-#[macro_export]
-macro_rules! ui_add_window {
-    ($identifier:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.ui_add_window($identifier.to_owned());
-        }
-    };
-}
+            /// Address the struct starts at.
+            pub const fn address(self) -> $crate::memory::Address {
+                self.0
+            }
 
-/// `ui::focus_window` function. Focuses the defined window if present.
-#[macro_export]
-macro_rules! ui_focus_window {
-    ($identifier:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.ui_focus_window($identifier.to_owned());
-        }
-    };
-}
+            $(
+                $(#[$field_meta])*
+                pub const fn $field(self) -> $crate::memory::Field<$ty> {
+                    $crate::memory::Field::new(self.0, $offset)
+                }
+            )*
 
-/// `ui::add_label(identifier, text)` function. Creates a new label with the specified content.
-#[macro_export]
-macro_rules! ui_add_label {
-    ($identifier:expr, $text:expr, $font_id:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.ui_add_label($identifier.to_owned(), $text.to_owned(), $font_id);
-        }
-    };
-}
+            /// Logs the address and every field of the struct, or why it couldn't be read.
+            pub fn dump(self) {
+                use ::std::fmt::Write;
 
-/// `ui::add_button(identifier, text, code)` function. Creates a new button with the specified
-/// text and Rune code.
-#[macro_export]
-macro_rules! ui_add_button {
-    ($identifier:expr, $text:expr, $source:expr, $callback:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.ui_add_button(
-                $identifier.to_owned(),
-                $text.to_owned(),
-                $source.to_owned(),
-                $callback,
-            );
-        }
-    };
-}
+                let mut dump = ::std::format!("{self:?}:");
+                $(
+                    let _ = match self.$field().get() {
+                        Ok(value) => ::std::write!(dump, "\n  {} = {value:?}", stringify!($field)),
+                        Err(error) => ::std::write!(dump, "\n  {} = <{error}>", stringify!($field)),
+                    };
+                )*
 
-/// `ui::add_separator(identifier)` function. Adds a new horizontal separator.
-#[macro_export]
-macro_rules! ui_add_separator {
-    ($identifier:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.ui_add_separator($identifier.to_owned());
+                $crate::log!(dump);
+            }
         }
-    };
-}
 
-/// `ui::add_spacing(identifier, x, y)` function. Adds spacing between widgets.
-#[macro_export]
-macro_rules! ui_add_spacing {
-    ($identifier:expr, $x:expr, $y:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.ui_add_spacing($identifier.to_owned(), $x, $y);
+        impl ::std::fmt::Debug for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                ::std::write!(f, "{}({:#x})", stringify!($name), self.0.get())
+            }
         }
-    };
-}
 
-/// `dynamic::create_thread_key(name)` function. Creates a globally-accessible thread-key.
-#[macro_export]
-macro_rules! create_thread_key {
-    ($identifier:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.create_thread_key($identifier.to_owned());
-        }
-    };
-}
+        /// Fields of this type are pointers to it.
+        impl $crate::memory::FieldValue for $name {
+            fn read_field(
+                address: $crate::memory::Address,
+            ) -> ::std::result::Result<Self, $crate::memory::MemoryError> {
+                let pointer = $crate::memory::read::<$crate::memory::Address>(address)?;
+                if pointer.is_null() {
+                    return Err($crate::memory::MemoryError::Null);
+                }
 
-/// `dynamic::set_thread_key_value(name, value)` function. Sets the value of a thread-key.
-#[macro_export]
-macro_rules! set_thread_key_value {
-    ($identifier:expr, $value:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.set_thread_key_value($identifier.to_owned(), $value);
+                Ok(Self(pointer))
+            }
         }
-    };
-}
 
-/// `dynamic::get_thread_key(name)` function. Returns the value of the thread-key.
-#[macro_export]
-macro_rules! get_thread_key {
-    ($identifier:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.get_thread_key($identifier.to_owned())
-        } else {
-            false
+        impl $crate::memory::FieldValueMut for $name {
+            fn write_field(
+                address: $crate::memory::Address,
+                value: Self,
+            ) -> ::std::result::Result<(), $crate::memory::MemoryError> {
+                $crate::memory::write_untracked(address, value.0)
+            }
         }
     };
 }
 
 /// Creates a specialized hook with a custom name, which upon setting the value to `true`, releases
 /// the DLL from the process and calls the `on_pre_eject` closure, where you perform any needed
-/// cleanup.
+/// cleanup. A panic in `on_pre_eject` is logged, and the DLL is released anyway.
+/// Every tracked memory write made through `arctic::memory` is undone before the DLL is released.
 #[macro_export]
 macro_rules! setup_auto_eject_tk_listener {
     ($identifier:expr, $process:expr, $payload:expr, $on_pre_eject:expr) => {
@@ -153,170 +156,56 @@ macro_rules! setup_auto_eject_tk_listener {
 
             #[allow(clippy::redundant_closure_call)]
             #[allow(unused_unsafe)]
-            unsafe {
-                $on_pre_eject();
+            let result = $crate::panic::catch_panic(|| unsafe { $on_pre_eject() });
+            if let Err(message) = result {
+                $crate::tracing::error!(
+                    "on_pre_eject panicked, ejecting anyway! Message: {message}"
+                );
             }
 
+            $crate::memory::revert_all();
             eject_payload!($process, $payload);
         });
     };
 }
 
+/// Turns a non-capturing closure into a `ButtonCallback` for `ui_add_button!`, which runs with
+/// panics contained since unwinding into the host would take the process down.
+#[macro_export]
+macro_rules! button_callback {
+    ($callback:expr) => {{
+        extern "C" fn callback() {
+            $crate::panic::guard("button callback", $callback, || ());
+        }
+
+        Some(callback as extern "C" fn())
+    }};
+}
+
 /// Enables a hook, returning an instance to the created `hook` regardless of if it fails or not.
+/// `$fn_address` is anything convertible into a `memory::Address`, such as a pointer or the result
+/// of `ModuleAddress::resolve`.
 #[macro_export]
 macro_rules! enable_hook {
     ($hook:expr, $fn_address:expr, $callback:expr, $hook_name:literal) => {
         unsafe {
-            let hook = $hook.initialize(std::mem::transmute($fn_address), $callback);
+            let address = $crate::memory::Address::from($fn_address);
+            let hook = $hook.initialize(std::mem::transmute(address.as_ptr::<()>()), $callback);
             if let Ok(hook) = hook {
                 if let Err(error) = hook.enable() {
-                    log!(&format!(
-                        "[ERROR] Failed enabling hook {}, error: {error}",
-                        $hook_name
-                    ));
+                    $crate::tracing::error!("Failed enabling hook {}, error: {error}", $hook_name);
                 } else {
-                    log!(&format!("Hook {} loaded successfully!", $hook_name));
+                    $crate::tracing::info!("Hook {} loaded successfully!", $hook_name);
                 }
             } else {
-                log!(&format!(
-                    "[ERROR] Failed initializing hook {}, error: {}",
+                $crate::tracing::error!(
+                    "Failed initializing hook {}, error: {}",
                     $hook_name,
                     hook.as_ref().unwrap_err_unchecked()
-                ));
+                );
             }
 
             hook
         }
     };
 }
-
-/// `ui::get_i32_slider_value(identifier)` function. Returns the i32 value of a defined slider.
-#[macro_export]
-macro_rules! get_i32_slider_value {
-    ($identifier:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.get_i32_slider_value($identifier.to_owned())
-        } else {
-            0
-        }
-    };
-}
-
-/// `ui::get_f32_slider_value(identifier)` function. Returns the f32 value of a defined slider.
-#[macro_export]
-macro_rules! get_f32_slider_value {
-    ($identifier:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.get_f32_slider_value($identifier.to_owned())
-        } else {
-            0.0
-        }
-    };
-}
-
-/// `ui::set_next_item_same_line(identifier)` function. Attempts to make the next upcoming
-/// widget on the currently-active line.
-#[macro_export]
-macro_rules! ui_set_next_item_same_line {
-    ($identifier:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.ui_set_next_item_same_line($identifier.to_owned());
-        }
-    };
-}
-
-/// `ui::add_custom_font_label(identifier, text, relative_font_path)` function. Adds a new
-/// label with a custom-loaded font.
-#[macro_export]
-macro_rules! ui_add_custom_font_label {
-    ($identifier:expr, $text:expr, $relative_font_path:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.ui_add_custom_font_label(
-                $identifier.to_owned(),
-                $text.to_owned(),
-                $relative_font_path.to_owned(),
-            );
-        }
-    };
-}
-
-/// `ui::remove_widget(identifier)` function. Attempts to remove the specified widget from the
-/// focused window.
-#[macro_export]
-macro_rules! ui_remove_widget {
-    ($identifier:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.ui_remove_widget($identifier.to_owned());
-        }
-    };
-}
-
-/// `ui::remove_all_widgets()` function. Removes all widgets from the focused window.
-#[macro_export]
-macro_rules! ui_remove_all_widgets {
-    () => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.ui_remove_all_widgets();
-        }
-    };
-}
-
-/// `ui::add_i32_slider(identifier, text, min, max, rune_code)` function. Adds a i32 slider to
-/// the UI with optional Rune code execution.
-#[macro_export]
-macro_rules! ui_add_i32_slider {
-    ($identifier:expr, $text:expr, $min:expr, $max:expr, $rune_code:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.ui_add_i32_slider(
-                $identifier.to_owned(),
-                $text.to_owned(),
-                $min,
-                $max,
-                $rune_code,
-            );
-        }
-    };
-}
-
-/// `ui::add_f32_slider(identifier, text, min, max, rune_code)` function. Adds a f32 slider to
-/// the UI with optional Rune code execution.
-#[macro_export]
-macro_rules! ui_add_f32_slider {
-    ($identifier:expr, $text:expr, $min:expr, $max:expr, $rune_code:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.ui_add_f32_slider(
-                $identifier.to_owned(),
-                $text.to_owned(),
-                $min,
-                $max,
-                $rune_code,
-            );
-        }
-    };
-}
-
-/// `Sellix::is_paying_for_product(product_id, bearer_tolen)` function. Checks if the user is
-/// paying for the specified Sellix product.
-#[macro_export]
-macro_rules! sellix_is_paying_for_product {
-    ($product_id:expr, $bearer_token:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.sellix_is_paying_for_product($product_id.to_owned(), $bearer_token.to_owned())
-        } else {
-            false
-        }
-    };
-}
-
-/// `Config::has_serial(serial)` function. Checks if the defined serial is present in the
-/// config.
-#[macro_export]
-macro_rules! config_has_serial {
-    ($serial:expr) => {
-        if let Some(functions) = $crate::functions::FUNCTIONS.get() {
-            functions.config_has_serial($serial.to_owned())
-        } else {
-            false
-        }
-    };
-}
@@ -0,0 +1,454 @@
+use std::{
+    fmt::Display,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+mod address;
+mod export;
+mod image;
+mod module;
+mod pointer;
+mod protect;
+mod region;
+mod remote;
+mod scan;
+mod string;
+mod value_scan;
+
+pub use address::{Address, InvalidModuleAddress, ModuleAddress, Rva};
+pub use export::{exports, read_exports, resolve_export, Export, ExportTarget};
+pub use image::image_size;
+pub use module::{find_module, module_base, modules, LoadedModule};
+pub use pointer::{load_pointer_chains, InvalidPointerChain, PointerChain};
+pub use region::{query, regions, Protection, Region};
+pub use remote::{Field, FieldValue, FieldValueMut, MAX_STRING_LEN};
+pub use scan::{resolve_relative, InvalidPattern, Pattern};
+pub use string::{
+    read_c_string, read_c_string_utf16, read_dotnet_string, read_msvc_string, read_prefixed_string,
+    read_utf16, read_utf8,
+};
+pub use value_scan::{Refinement, Scannable, ValueScan, MAX_CANDIDATES};
+
+/// Plain data which can be read from and written to memory byte by byte.
+///
+/// # Safety
+/// Every bit pattern has to be a valid value of the type, and the type mustn't have any padding.
+/// This holds for `#[repr(C)]` structs whose fields are all `Pod` and which have no gaps between
+/// fields or at the end.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($ty:ty),*) => {
+        $(
+            // SAFETY: Integers, floats and addresses have neither invalid bit patterns nor padding.
+            unsafe impl Pod for $ty {}
+        )*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, Address);
+
+// SAFETY: Arrays have no padding between their elements.
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// Reason why memory couldn't be accessed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MemoryError {
+    /// The address is null.
+    Null,
+
+    /// The address isn't aligned to what the type read needs.
+    Misaligned { address: Address, align: usize },
+
+    /// The range of `len` bytes starting at `address` wraps around the address space.
+    Overflow { address: Address, len: usize },
+
+    /// Some of the `len` bytes starting at `address` aren't mapped, or not for the kind of access.
+    InvalidAddress { address: Address, len: usize },
+
+    /// There's no valid PE image at the address.
+    InvalidImage(Address),
+
+    /// No module with the name is loaded.
+    ModuleNotFound(String),
+
+    /// The string at `address` is longer than the `max_len` it was read with.
+    StringTooLong { address: Address, max_len: usize },
+
+    /// The string at the address isn't valid UTF-8 or UTF-16, or its length is corrupt.
+    InvalidString(Address),
+
+    /// The module doesn't export a symbol with the name.
+    ExportNotFound { module: String, name: String },
+}
+
+impl Display for MemoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => write!(f, "null address"),
+            Self::Misaligned { address, align } => {
+                write!(
+                    f,
+                    "address {:#x} isn't aligned to {align} bytes",
+                    address.get()
+                )
+            }
+            Self::Overflow { address, len } => write!(
+                f,
+                "{len} bytes at {:#x} wrap around the address space",
+                address.get()
+            ),
+            Self::InvalidAddress { address, len } => {
+                write!(f, "{len} bytes at {:#x} aren't accessible", address.get())
+            }
+            Self::InvalidImage(address) => {
+                write!(f, "no valid PE image at {:#x}", address.get())
+            }
+            Self::ModuleNotFound(name) => write!(f, "no module called {name:?} is loaded"),
+            Self::StringTooLong { address, max_len } => {
+                write!(f, "string at {:#x} is longer than {max_len}", address.get())
+            }
+            Self::InvalidString(address) => {
+                write!(f, "no valid string at {:#x}", address.get())
+            }
+            Self::ExportNotFound { module, name } => {
+                write!(f, "{module:?} doesn't export {name:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MemoryError {}
+
+/// Checks that `address` is non-null and aligned to `align`.
+fn check_pointer(address: Address, align: usize) -> Result<(), MemoryError> {
+    if address.is_null() {
+        return Err(MemoryError::Null);
+    }
+
+    if !address.is_aligned_to(align) {
+        return Err(MemoryError::Misaligned { address, align });
+    }
+
+    Ok(())
+}
+
+/// Checks that `len` bytes at `address` are readable, and that `address` is aligned to `align`.
+fn check_readable(address: Address, len: usize, align: usize) -> Result<(), MemoryError> {
+    check_pointer(address, align)?;
+    region::check(address, len, |protection| protection.read)
+}
+
+/// Bytes read at once by `read_slice`, so that a corrupt length fails before it's all allocated.
+const READ_CHUNK_SIZE: usize = 1 << 16;
+
+/// A `T` whose bytes are all zero, to read into.
+fn zeroed<T: Pod>() -> T {
+    // SAFETY: Every bit pattern is a valid `T`, including zeros.
+    unsafe { std::mem::zeroed() }
+}
+
+/// Bytes of `values`, which can be read into.
+fn bytes_of_mut<T: Pod>(values: &mut [T]) -> &mut [u8] {
+    // SAFETY: `Pod` types have no padding and every bit pattern is valid, so their bytes can be
+    // written freely.
+    unsafe {
+        std::slice::from_raw_parts_mut(values.as_mut_ptr().cast(), std::mem::size_of_val(values))
+    }
+}
+
+/// Reads a `T` at `address`, which has to be aligned for `T`.
+pub fn read<T: Pod>(address: Address) -> Result<T, MemoryError> {
+    check_pointer(address, align_of::<T>())?;
+    read_unaligned(address)
+}
+
+/// Reads a `T` at `address`, regardless of its alignment, such as a field of a packed struct.
+pub fn read_unaligned<T: Pod>(address: Address) -> Result<T, MemoryError> {
+    check_pointer(address, 1)?;
+    let mut value = [zeroed::<T>()];
+    region::read_into(address, bytes_of_mut(&mut value))?;
+    Ok(value[0])
+}
+
+/// Reads `len` consecutive `T`s starting at `address`, which has to be aligned for `T`.
+pub fn read_slice<T: Pod>(address: Address, len: usize) -> Result<Vec<T>, MemoryError> {
+    let size = len
+        .checked_mul(size_of::<T>())
+        .ok_or(MemoryError::Overflow { address, len })?;
+
+    check_pointer(address, align_of::<T>())?;
+    let chunk_len = (READ_CHUNK_SIZE / size_of::<T>().max(1)).max(1);
+    let mut values = Vec::new();
+    while values.len() < len {
+        let read = values.len();
+        values.resize(read + (len - read).min(chunk_len), zeroed());
+        let from = Address::new(address.get() + read * size_of::<T>());
+        region::read_into(from, bytes_of_mut(&mut values[read..])).map_err(
+            |error| match error {
+                MemoryError::InvalidAddress { .. } => {
+                    MemoryError::InvalidAddress { address, len: size }
+                }
+                error => error,
+            },
+        )?;
+    }
+
+    Ok(values)
+}
+
+/// Fills `buffer` with the bytes starting at `address`.
+pub fn read_bytes_into(address: Address, buffer: &mut [u8]) -> Result<(), MemoryError> {
+    check_pointer(address, 1)?;
+    region::read_into(address, buffer)
+}
+
+/// Bytes overwritten by the writes to a range, see `revert`.
+struct Patch {
+    address: Address,
+
+    /// Bytes from before the first write.
+    original: Vec<u8>,
+
+    /// Bytes left behind by the latest writes.
+    written: Vec<u8>,
+}
+
+impl Patch {
+    /// Address right after the patch.
+    fn end(&self) -> usize {
+        self.address.get() + self.original.len()
+    }
+
+    fn contains(&self, address: Address) -> bool {
+        self.address <= address && address.get() < self.end()
+    }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        self.address.get() < other.end() && other.address.get() < self.end()
+    }
+
+    /// Patch covering both `earlier` and `later`, keeping the original bytes of `earlier` and the
+    /// written bytes of `later` where they overlap.
+    fn merge(earlier: Self, later: Self) -> Self {
+        let start = earlier.address.min(later.address);
+        let len = earlier.end().max(later.end()) - start.get();
+        let place = |patch: &Self| patch.address.get() - start.get()..patch.end() - start.get();
+        let mut merged = Self {
+            address: start,
+            original: vec![0; len],
+            written: vec![0; len],
+        };
+
+        merged.original[place(&later)].copy_from_slice(&later.original);
+        merged.original[place(&earlier)].copy_from_slice(&earlier.original);
+        merged.written[place(&earlier)].copy_from_slice(&earlier.written);
+        merged.written[place(&later)].copy_from_slice(&later.written);
+        merged
+    }
+}
+
+/// Ranges written so far, which never overlap since overlapping writes are merged.
+static JOURNAL: Mutex<Vec<Patch>> = Mutex::new(Vec::new());
+
+/// Locks `JOURNAL`, which is also held while writing so that protection changes don't race.
+fn journal() -> MutexGuard<'static, Vec<Patch>> {
+    JOURNAL.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Adds `patch` to the journal, merging it with the patches it overlaps.
+fn record(journal: &mut Vec<Patch>, mut patch: Patch) {
+    let mut index = 0;
+    while index < journal.len() {
+        if journal[index].overlaps(&patch) {
+            patch = Patch::merge(journal.swap_remove(index), patch);
+        } else {
+            index += 1;
+        }
+    }
+
+    journal.push(patch);
+}
+
+/// Bytes of `value`.
+fn bytes_of<T: Pod>(value: &T) -> &[u8] {
+    // SAFETY: `Pod` types have no padding, so every byte of `value` is initialized.
+    unsafe { std::slice::from_raw_parts(std::ptr::from_ref(value).cast::<u8>(), size_of::<T>()) }
+}
+
+/// Overwrites the bytes at `address` with `bytes`, lifting the page protection if they aren't
+/// readable and writable, and returns the bytes it replaced.
+fn overwrite(address: Address, bytes: &[u8]) -> Result<Vec<u8>, MemoryError> {
+    // Most writes go to data which is readable and writable already, which doesn't need the memory
+    // map to be queried.
+    let mut original = vec![0; bytes.len()];
+    let readable = region::read_into(address, &mut original).is_ok();
+    if !readable || region::write_from(address, bytes).is_err() {
+        let _unprotected = protect::unprotect(address, bytes.len())?;
+        if !readable {
+            region::read_into(address, &mut original)?;
+        }
+
+        region::write_from(address, bytes)?;
+    }
+
+    protect::flush_instruction_cache(address, bytes.len());
+    Ok(original)
+}
+
+/// Writes `value` at `address`, which has to be aligned for `T`, see `write_bytes`.
+pub fn write<T: Pod>(address: Address, value: T) -> Result<(), MemoryError> {
+    check_pointer(address, align_of::<T>())?;
+    write_bytes(address, bytes_of(&value))
+}
+
+/// Writes `bytes` at `address`, such as a patch to the game's code.
+/// Read-only and executable pages are made writable for the duration of the write and get their
+/// protection back afterwards. The original bytes are recorded, so that the write can be undone
+/// through `revert`, and every write is undone through `revert_all` once the payload is ejected.
+/// Use `write_bytes_untracked` for the game's data instead, which may be freed before then.
+pub fn write_bytes(address: Address, bytes: &[u8]) -> Result<(), MemoryError> {
+    check_pointer(address, 1)?;
+    let mut journal = journal();
+    let original = overwrite(address, bytes)?;
+    let patch = Patch {
+        address,
+        original,
+        written: bytes.to_vec(),
+    };
+
+    record(&mut journal, patch);
+    Ok(())
+}
+
+/// Writes `value` at `address`, which has to be aligned for `T`, see `write_bytes_untracked`.
+pub fn write_untracked<T: Pod>(address: Address, value: T) -> Result<(), MemoryError> {
+    check_pointer(address, align_of::<T>())?;
+    write_bytes_untracked(address, bytes_of(&value))
+}
+
+/// Writes `bytes` at `address` like `write_bytes`, but without recording the original bytes, so
+/// that the write is never undone. Meant for the game's data, such as the fields of objects which
+/// may be freed before the payload is ejected.
+pub fn write_bytes_untracked(address: Address, bytes: &[u8]) -> Result<(), MemoryError> {
+    check_pointer(address, 1)?;
+    let _journal = journal();
+    overwrite(address, bytes).map(drop)
+}
+
+/// Undoes the writes to the range containing `address`, restoring the bytes from before the first
+/// one. Writes which overlap are undone together.
+/// Returns `false` if nothing has been written there.
+pub fn revert(address: Address) -> Result<bool, MemoryError> {
+    let mut journal = journal();
+    let Some(index) = journal.iter().position(|patch| patch.contains(address)) else {
+        return Ok(false);
+    };
+
+    overwrite(journal[index].address, &journal[index].original)?;
+    journal.swap_remove(index);
+    Ok(true)
+}
+
+/// Undoes every write made so far, which happens on its own once the payload is ejected.
+/// Ranges which don't hold what was written anymore, such as memory which has been freed and
+/// reused, or which the game has changed since, are left alone.
+pub fn revert_all() {
+    let mut journal = journal();
+    for patch in journal.drain(..) {
+        let mut current = vec![0; patch.written.len()];
+        let result = region::read_into(patch.address, &mut current).and_then(|()| {
+            if current != patch.written {
+                tracing::warn!(
+                    "Didn't revert the write at {:#x}, it has been overwritten since",
+                    patch.address.get()
+                );
+
+                return Ok(());
+            }
+
+            overwrite(patch.address, &patch.original).map(drop)
+        });
+
+        if let Err(error) = result {
+            tracing::warn!(
+                "Couldn't revert the write at {:#x}, {error}",
+                patch.address.get()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockHost;
+
+    /// Number of journaled ranges overlapping `bytes`.
+    fn patches(bytes: &[u8]) -> usize {
+        let start = bytes.as_ptr() as usize;
+        let end = start + bytes.len();
+        journal()
+            .iter()
+            .filter(|patch| patch.address.get() < end && start < patch.end())
+            .count()
+    }
+
+    #[test]
+    fn overlapping_writes_are_reverted_together() {
+        let _host = MockHost::new();
+        let buffer = Box::new([0u8; 8]);
+        let address = Address::from_ptr(buffer.as_ptr());
+        let at = |offset| address.checked_add(offset).unwrap();
+        write_bytes(at(1), &[1; 4]).unwrap();
+        write_bytes(at(3), &[2; 4]).unwrap();
+        write_bytes(at(0), &[3; 2]).unwrap();
+        assert_eq!(*buffer, [3, 3, 1, 2, 2, 2, 2, 0]);
+        assert_eq!(patches(&*buffer), 1);
+
+        assert_eq!(revert(at(6)), Ok(true));
+        assert_eq!(*buffer, [0; 8]);
+        assert_eq!(revert(at(0)), Ok(false));
+    }
+
+    #[test]
+    fn repeated_writes_dont_grow_the_journal() {
+        let _host = MockHost::new();
+        let buffer = Box::new([0u32; 2]);
+        let address = Address::from_ptr(buffer.as_ptr());
+        for value in 1..100 {
+            write(address, value).unwrap();
+        }
+
+        assert_eq!(buffer[0], 99);
+        assert_eq!(patches(bytes_of(&*buffer)), 1);
+        assert_eq!(revert(address), Ok(true));
+        assert_eq!(*buffer, [0, 0]);
+    }
+
+    #[test]
+    fn untracked_writes_are_never_reverted() {
+        let _host = MockHost::new();
+        let buffer = Box::new([0u8; 4]);
+        let address = Address::from_ptr(buffer.as_ptr());
+        write_untracked(address, 7u32).unwrap();
+        assert_eq!(*buffer, 7u32.to_ne_bytes());
+        assert_eq!(patches(&*buffer), 0);
+        assert_eq!(revert(address), Ok(false));
+    }
+
+    #[test]
+    fn memory_changed_since_isnt_reverted() {
+        let _host = MockHost::new();
+        let mut buffer = Box::new([0u8; 4]);
+        let address = Address::from_ptr(buffer.as_ptr());
+        write_bytes(address, &[1, 1]).unwrap();
+        write_bytes(address.checked_add(2).unwrap(), &[2, 2]).unwrap();
+
+        // The game reuses the first range for something else.
+        buffer[0] = 5;
+        revert_all();
+        assert_eq!(*buffer, [5, 1, 0, 0]);
+        assert_eq!(patches(&*buffer), 0);
+    }
+}
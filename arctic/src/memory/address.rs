@@ -0,0 +1,343 @@
+use super::{module_base, MemoryError};
+use std::{fmt::Display, str::FromStr};
+
+/// Address in the process' memory, formatted in hexadecimal.
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Address(usize);
+
+impl Address {
+    /// The null address.
+    pub const NULL: Self = Self(0);
+
+    pub const fn new(address: usize) -> Self {
+        Self(address)
+    }
+
+    /// Address `pointer` points to.
+    pub fn from_ptr<T: ?Sized>(pointer: *const T) -> Self {
+        Self(pointer.cast::<()>() as usize)
+    }
+
+    /// The address as an integer.
+    pub const fn get(self) -> usize {
+        self.0
+    }
+
+    pub const fn is_null(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Pointer to the address, which may or may not point to a valid `T`.
+    pub const fn as_ptr<T>(self) -> *const T {
+        self.0 as *const T
+    }
+
+    /// Mutable pointer to the address, which may or may not point to a valid `T`.
+    pub const fn as_mut_ptr<T>(self) -> *mut T {
+        self.0 as *mut T
+    }
+
+    /// Address `offset` bytes further, `None` if that wraps around the address space.
+    pub const fn checked_add(self, offset: usize) -> Option<Self> {
+        match self.0.checked_add(offset) {
+            Some(address) => Some(Self(address)),
+            None => None,
+        }
+    }
+
+    /// Address `offset` bytes further or back, `None` if that wraps around the address space.
+    pub const fn checked_add_signed(self, offset: isize) -> Option<Self> {
+        match self.0.checked_add_signed(offset) {
+            Some(address) => Some(Self(address)),
+            None => None,
+        }
+    }
+
+    /// Address `offset` bytes back, `None` if that wraps around the address space.
+    pub const fn checked_sub(self, offset: usize) -> Option<Self> {
+        match self.0.checked_sub(offset) {
+            Some(address) => Some(Self(address)),
+            None => None,
+        }
+    }
+
+    /// How many bytes past `base` the address is, `None` if it's before `base`.
+    pub const fn offset_from(self, base: Self) -> Option<usize> {
+        self.0.checked_sub(base.0)
+    }
+
+    /// Checks whether the address is a multiple of `align`, which has to be a power of two.
+    pub const fn is_aligned_to(self, align: usize) -> bool {
+        self.0 & (align - 1) == 0
+    }
+}
+
+impl From<usize> for Address {
+    fn from(address: usize) -> Self {
+        Self(address)
+    }
+}
+
+impl From<Address> for usize {
+    fn from(address: Address) -> Self {
+        address.0
+    }
+}
+
+/// Base address of the module, which is what its handle is on Windows.
+impl From<crate::abi::ModuleHandle> for Address {
+    fn from(module: crate::abi::ModuleHandle) -> Self {
+        Self::from_ptr(module.0)
+    }
+}
+
+impl<T: ?Sized> From<*const T> for Address {
+    fn from(pointer: *const T) -> Self {
+        Self::from_ptr(pointer)
+    }
+}
+
+impl<T: ?Sized> From<*mut T> for Address {
+    fn from(pointer: *mut T) -> Self {
+        Self::from_ptr(pointer)
+    }
+}
+
+impl std::fmt::Debug for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Address({:#x})", self.0)
+    }
+}
+
+/// Formats the address as `0x7ff6a0001000`.
+impl Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+impl std::fmt::LowerHex for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl std::fmt::UpperHex for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::UpperHex::fmt(&self.0, f)
+    }
+}
+
+/// Parses a hexadecimal number, with or without `0x`.
+pub(super) fn parse_hex(number: &str) -> Option<usize> {
+    let number = number.trim();
+    let digits = number
+        .strip_prefix("0x")
+        .or_else(|| number.strip_prefix("0X"))
+        .unwrap_or(number);
+
+    // `from_str_radix` would accept a sign, such as `+10`.
+    if !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    usize::from_str_radix(digits, 16).ok()
+}
+
+/// Relative virtual address, an offset from the base of a PE image as found in its headers.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rva(pub u32);
+
+impl Rva {
+    /// Address the RVA points to in the image loaded at `base`.
+    pub fn resolve_in(self, base: Address) -> Result<Address, MemoryError> {
+        base.checked_add(self.0 as usize)
+            .ok_or(MemoryError::Overflow {
+                address: base,
+                len: self.0 as usize,
+            })
+    }
+}
+
+/// Address relative to the base of a loaded module, such as `GameAssembly.dll+0x1A2B30`, which
+/// survives ASLR and can be stored in configs.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ModuleAddress {
+    module: String,
+    offset: usize,
+}
+
+impl ModuleAddress {
+    /// Address `offset` bytes into the module called `module`.
+    pub fn new(module: impl Into<String>, offset: usize) -> Self {
+        Self {
+            module: module.into(),
+            offset,
+        }
+    }
+
+    /// Name of the module, such as `GameAssembly.dll`.
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    /// Offset from the module's base.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Address in the module as it's currently loaded.
+    pub fn resolve(&self) -> Result<Address, MemoryError> {
+        let base = module_base(&self.module)
+            .ok_or_else(|| MemoryError::ModuleNotFound(self.module.clone()))?;
+
+        base.checked_add(self.offset).ok_or(MemoryError::Overflow {
+            address: base,
+            len: self.offset,
+        })
+    }
+}
+
+/// Formats the address the way it's parsed, such as `GameAssembly.dll+0x1A2B30`.
+impl Display for ModuleAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}+{:#x}", self.module, self.offset)
+    }
+}
+
+/// Parses addresses such as `GameAssembly.dll+0x1A2B30`, where the offset is hexadecimal, with or
+/// without `0x`.
+impl FromStr for ModuleAddress {
+    type Err = InvalidModuleAddress;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidModuleAddress(address.to_owned());
+        let (module, offset) = address.trim().rsplit_once('+').ok_or_else(invalid)?;
+        let module = module.trim();
+        if module.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Self::new(module, parse_hex(offset).ok_or_else(invalid)?))
+    }
+}
+
+/// A module-relative address which couldn't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidModuleAddress(pub String);
+
+impl Display for InvalidModuleAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid address {:?}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidModuleAddress {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_addresses_round_trip() {
+        let address: ModuleAddress = "GameAssembly.dll+0x1A2B30".parse().unwrap();
+        assert_eq!(address, ModuleAddress::new("GameAssembly.dll", 0x1A2B30));
+        assert_eq!(address.to_string(), "GameAssembly.dll+0x1a2b30");
+        assert_eq!(address.to_string().parse(), Ok(address.clone()));
+        assert_eq!(" GameAssembly.dll + 1A2B30 ".parse(), Ok(address));
+
+        let plus: ModuleAddress = "lib+game.so+0x10".parse().unwrap();
+        assert_eq!((plus.module(), plus.offset()), ("lib+game.so", 0x10));
+    }
+
+    #[test]
+    fn malformed_module_addresses_are_refused() {
+        for malformed in [
+            "",
+            "GameAssembly.dll",
+            "GameAssembly.dll+",
+            "+0x10",
+            "GameAssembly.dll+0x",
+            "GameAssembly.dll+-0x10",
+            "GameAssembly.dll+0x1G",
+            "GameAssembly.dll+0x10000000000000000",
+        ] {
+            assert_eq!(
+                malformed.parse::<ModuleAddress>(),
+                Err(InvalidModuleAddress(malformed.to_owned())),
+                "{malformed:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn addresses_are_formatted_in_hexadecimal() {
+        let address = Address::new(0x7FF6_A000_1000);
+        assert_eq!(address.to_string(), "0x7ff6a0001000");
+        assert_eq!(format!("{address:?}"), "Address(0x7ff6a0001000)");
+        assert_eq!(format!("{address:X}"), "7FF6A0001000");
+    }
+
+    #[test]
+    fn arithmetic_is_checked() {
+        let max = Address::new(usize::MAX);
+        assert_eq!(max.checked_add(1), None);
+        assert_eq!(max.checked_add(0), Some(max));
+        assert_eq!(
+            Address::new(0x10).checked_add(0x10),
+            Some(Address::new(0x20))
+        );
+        assert_eq!(Address::NULL.checked_sub(1), None);
+        assert_eq!(Address::new(0x10).checked_sub(0x10), Some(Address::NULL));
+        assert_eq!(Address::new(0x10).checked_add_signed(-0x11), None);
+        assert_eq!(max.checked_add_signed(1), None);
+        assert_eq!(
+            Address::new(0x10).checked_add_signed(-0x8),
+            Some(Address::new(0x8))
+        );
+        assert_eq!(
+            Address::new(0x18).offset_from(Address::new(0x10)),
+            Some(0x8)
+        );
+        assert_eq!(Address::new(0x8).offset_from(Address::new(0x10)), None);
+        assert!(Address::new(0x18).is_aligned_to(8) && !Address::new(0x18).is_aligned_to(16));
+    }
+
+    #[test]
+    fn resolving_checks_for_overflow() {
+        let max = Address::new(usize::MAX);
+        assert_eq!(
+            Rva(1).resolve_in(max),
+            Err(MemoryError::Overflow {
+                address: max,
+                len: 1
+            })
+        );
+        assert_eq!(
+            Rva(0x10).resolve_in(Address::new(0x1000)),
+            Ok(Address::new(0x1010))
+        );
+
+        assert_eq!(
+            ModuleAddress::new("missing.dll", 0).resolve(),
+            Err(MemoryError::ModuleNotFound("missing.dll".to_owned()))
+        );
+
+        let exe = std::env::current_exe().unwrap();
+        let exe = exe.file_name().unwrap().to_str().unwrap();
+        let base = module_base(exe).unwrap();
+        assert_eq!(
+            ModuleAddress::new(exe, 0x10).resolve(),
+            Ok(base.checked_add(0x10).unwrap())
+        );
+        assert_eq!(
+            ModuleAddress::new(exe, usize::MAX).resolve(),
+            Err(MemoryError::Overflow {
+                address: base,
+                len: usize::MAX
+            })
+        );
+    }
+}
@@ -0,0 +1,356 @@
+use super::{
+    image::{nt_headers, File, Image, Loaded, NT_OPTIONAL_HEADER},
+    module_base, Address, MemoryError, Rva,
+};
+use std::path::Path;
+
+/// Offsets of the export directory's entry in the optional header of 32 and 64-bit images.
+const PE32_EXPORT_DIRECTORY: u32 = 0x60;
+const PE32_PLUS_EXPORT_DIRECTORY: u32 = 0x70;
+
+/// `Magic` of the optional header of 32 and 64-bit images.
+const PE32_MAGIC: u16 = 0x10B;
+const PE32_PLUS_MAGIC: u16 = 0x20B;
+
+/// Ordinals are 16-bit, so no image exports more functions than this.
+const MAX_EXPORTS: u32 = 1 << 16;
+
+/// Forwarders followed by `resolve_export` before giving up, since they could form a loop.
+const MAX_FORWARDS: usize = 8;
+
+/// Symbol exported by a PE image.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Export {
+    /// Name of the symbol, `None` for symbols which are only exported by ordinal.
+    pub name: Option<String>,
+
+    pub ordinal: u32,
+
+    pub target: ExportTarget,
+}
+
+/// What an export refers to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ExportTarget {
+    /// Code or data within the image.
+    Rva(Rva),
+
+    /// Export of another module, such as `NTDLL.RtlAllocateHeap` or `NTDLL.#12` by ordinal.
+    Forwarder(String),
+}
+
+/// Parses the export table of `image`.
+fn parse_exports(image: &impl Image) -> Result<Vec<Export>, MemoryError> {
+    let optional_header = nt_headers(image)?.wrapping_add(NT_OPTIONAL_HEADER);
+    let entry = match image.read::<u16>(optional_header)? {
+        PE32_MAGIC => optional_header.wrapping_add(PE32_EXPORT_DIRECTORY),
+        PE32_PLUS_MAGIC => optional_header.wrapping_add(PE32_PLUS_EXPORT_DIRECTORY),
+        _ => return Err(image.invalid()),
+    };
+
+    let directory = image.read::<u32>(entry)?;
+    let directory_size = image.read::<u32>(entry.wrapping_add(4))?;
+    if directory == 0 {
+        return Ok(Vec::new());
+    }
+
+    let field = |offset: u32| image.read::<u32>(directory.wrapping_add(offset));
+    let (ordinal_base, function_count, name_count) = (field(0x10)?, field(0x14)?, field(0x18)?);
+    let (functions, names, name_ordinals) = (field(0x1C)?, field(0x20)?, field(0x24)?);
+    if function_count > MAX_EXPORTS || name_count > MAX_EXPORTS {
+        return Err(image.invalid());
+    }
+
+    let mut exports = Vec::with_capacity(function_count as usize);
+    for index in 0..function_count {
+        let rva = image.read::<u32>(functions.wrapping_add(index * 4))?;
+
+        // Functions whose RVA points into the export directory are forwarded.
+        let target = if rva.wrapping_sub(directory) < directory_size {
+            ExportTarget::Forwarder(image.name(rva)?)
+        } else {
+            ExportTarget::Rva(Rva(rva))
+        };
+
+        exports.push(Export {
+            name: None,
+            ordinal: ordinal_base.wrapping_add(index),
+            target,
+        });
+    }
+
+    for index in 0..name_count {
+        let name = image.name(image.read::<u32>(names.wrapping_add(index * 4))?)?;
+        let function = image.read::<u16>(name_ordinals.wrapping_add(index * 2))?;
+        exports
+            .get_mut(usize::from(function))
+            .ok_or_else(|| image.invalid())?
+            .name = Some(name);
+    }
+
+    // Unused slots of the function table.
+    exports.retain(|export| export.target != ExportTarget::Rva(Rva(0)));
+    Ok(exports)
+}
+
+/// Every symbol exported by the PE image loaded at `base`, such as a module's base address.
+pub fn exports(base: Address) -> Result<Vec<Export>, MemoryError> {
+    parse_exports(&Loaded(base))
+}
+
+/// Every symbol exported by the PE file at `path`, such as a DLL on disk.
+pub fn read_exports(path: impl AsRef<Path>) -> std::io::Result<Vec<Export>> {
+    let bytes = std::fs::read(path)?;
+    File::new(&bytes)
+        .and_then(|file| parse_exports(&file))
+        .map_err(|error| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("invalid PE file, {error}"),
+            )
+        })
+}
+
+/// Address of the symbol called `name` exported by the loaded module called `module`, such as
+/// `resolve_export("kernel32.dll", "Sleep")`, for hooking it through `enable_hook!`.
+/// Forwarded exports are followed to the module they're forwarded to.
+pub fn resolve_export(module: &str, name: &str) -> Result<Address, MemoryError> {
+    let not_found = || MemoryError::ExportNotFound {
+        module: module.to_owned(),
+        name: name.to_owned(),
+    };
+
+    let (mut module, mut name) = (module.to_owned(), name.to_owned());
+    for _ in 0..=MAX_FORWARDS {
+        let base =
+            module_base(&module).ok_or_else(|| MemoryError::ModuleNotFound(module.clone()))?;
+
+        // Forwarders refer to exports by ordinal as `#12`.
+        let ordinal = name
+            .strip_prefix('#')
+            .and_then(|ordinal| ordinal.parse().ok());
+        let export = exports(base)?
+            .into_iter()
+            .find(|export| match ordinal {
+                Some(ordinal) => export.ordinal == ordinal,
+                None => export.name.as_deref() == Some(&*name),
+            })
+            .ok_or_else(not_found)?;
+
+        match export.target {
+            ExportTarget::Rva(rva) => return rva.resolve_in(base),
+            ExportTarget::Forwarder(forwarder) => {
+                // Such as `NTDLL.RtlAllocateHeap`, where the module's extension is left out.
+                let (forwarded_module, forwarded_name) =
+                    forwarder.rsplit_once('.').ok_or_else(not_found)?;
+
+                module = format!("{forwarded_module}.dll");
+                name = forwarded_name.to_owned();
+            }
+        }
+    }
+
+    Err(not_found())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::image::tests::{file_offset, loaded, pe_file, put, SECTION_RVA};
+
+    /// RVAs of the parts of the export directory written by `with_exports`.
+    const DIRECTORY: u32 = SECTION_RVA;
+    const FUNCTIONS: u32 = DIRECTORY + 0x28;
+    const NAMES: u32 = DIRECTORY + 0x38;
+    const NAME_ORDINALS: u32 = DIRECTORY + 0x40;
+    const STRINGS: u32 = DIRECTORY + 0x50;
+    const DIRECTORY_SIZE: u32 = 0x100;
+
+    /// Writes the `u32` `value` at `rva` within the section of `file`.
+    fn put_u32(file: &mut [u8], rva: u32, value: u32) {
+        put(file, file_offset(rva), &value.to_le_bytes());
+    }
+
+    /// 64-bit PE file exporting `Alpha` by name, a function only by ordinal, `Forwarded` as a
+    /// forwarder to `NTDLL.#12`, and an unused slot, starting at ordinal 1.
+    fn with_exports() -> Vec<u8> {
+        let mut file = pe_file();
+        put(
+            &mut file,
+            0x58 + PE32_PLUS_EXPORT_DIRECTORY as usize,
+            &DIRECTORY.to_le_bytes(),
+        );
+        put(
+            &mut file,
+            0x58 + PE32_PLUS_EXPORT_DIRECTORY as usize + 4,
+            &DIRECTORY_SIZE.to_le_bytes(),
+        );
+
+        let (alpha, forwarded, forwarder) = (STRINGS, STRINGS + 0x8, STRINGS + 0x18);
+        put(&mut file, file_offset(alpha), b"Alpha\0");
+        put(&mut file, file_offset(forwarded), b"Forwarded\0");
+        put(&mut file, file_offset(forwarder), b"NTDLL.#12\0");
+
+        put_u32(&mut file, DIRECTORY + 0x10, 1);
+        put_u32(&mut file, DIRECTORY + 0x14, 4);
+        put_u32(&mut file, DIRECTORY + 0x18, 2);
+        put_u32(&mut file, DIRECTORY + 0x1C, FUNCTIONS);
+        put_u32(&mut file, DIRECTORY + 0x20, NAMES);
+        put_u32(&mut file, DIRECTORY + 0x24, NAME_ORDINALS);
+        for (index, rva) in [0x2000, 0x2010, forwarder, 0].into_iter().enumerate() {
+            put_u32(&mut file, FUNCTIONS + index as u32 * 4, rva);
+        }
+
+        put_u32(&mut file, NAMES, alpha);
+        put_u32(&mut file, NAMES + 4, forwarded);
+        put(&mut file, file_offset(NAME_ORDINALS), &[0, 0, 2, 0]);
+        file
+    }
+
+    fn expected() -> Vec<Export> {
+        vec![
+            Export {
+                name: Some("Alpha".to_owned()),
+                ordinal: 1,
+                target: ExportTarget::Rva(Rva(0x2000)),
+            },
+            Export {
+                name: None,
+                ordinal: 2,
+                target: ExportTarget::Rva(Rva(0x2010)),
+            },
+            Export {
+                name: Some("Forwarded".to_owned()),
+                ordinal: 3,
+                target: ExportTarget::Forwarder("NTDLL.#12".to_owned()),
+            },
+        ]
+    }
+
+    fn parse_file(bytes: &[u8]) -> Result<Vec<Export>, MemoryError> {
+        parse_exports(&File::new(bytes)?)
+    }
+
+    /// `read_exports` for a file holding `bytes`.
+    fn read_exports_of(bytes: &[u8]) -> std::io::Result<Vec<Export>> {
+        let path = std::env::temp_dir().join(format!(
+            "arctic-exports-{}-{}.dll",
+            std::process::id(),
+            bytes.len()
+        ));
+        std::fs::write(&path, bytes)?;
+        let exports = read_exports(&path);
+        std::fs::remove_file(&path)?;
+        exports
+    }
+
+    #[test]
+    fn exports_are_read_from_files() {
+        assert_eq!(parse_file(&with_exports()), Ok(expected()));
+        assert_eq!(parse_file(&pe_file()), Ok(Vec::new()));
+        assert_eq!(read_exports_of(&with_exports()).unwrap(), expected());
+    }
+
+    #[test]
+    fn exports_are_read_from_fixture_dlls() {
+        // Built by `fixtures/build.sh`, as PE32 and PE32+ respectively.
+        for (name, magic) in [("fixture32.dll", 0x10B), ("fixture64.dll", 0x20B)] {
+            let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("fixtures")
+                .join(name);
+            let bytes = std::fs::read(&path).unwrap();
+            let headers = u32::from_le_bytes(bytes[0x3C..0x40].try_into().unwrap()) as usize;
+            assert_eq!(
+                bytes[headers + 0x18..headers + 0x1A],
+                u16::to_le_bytes(magic)
+            );
+
+            assert_eq!(
+                read_exports(&path).unwrap(),
+                [
+                    Export {
+                        name: Some("arctic_fixture_answer".to_owned()),
+                        ordinal: 1,
+                        target: ExportTarget::Rva(Rva(0x1000)),
+                    },
+                    Export {
+                        name: None,
+                        ordinal: 2,
+                        target: ExportTarget::Rva(Rva(0x1010)),
+                    },
+                    Export {
+                        name: Some("arctic_fixture_sleep".to_owned()),
+                        ordinal: 3,
+                        target: ExportTarget::Forwarder("kernel32.Sleep".to_owned()),
+                    },
+                ],
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn exports_are_read_from_loaded_images() {
+        let image = loaded(&with_exports());
+        assert_eq!(exports(Address::from_ptr(image.as_ptr())), Ok(expected()));
+    }
+
+    #[test]
+    fn truncated_directories_are_refused() {
+        let file = with_exports();
+        let truncated = &file[..file_offset(FUNCTIONS + 4)];
+        assert_eq!(
+            parse_file(truncated),
+            Err(MemoryError::InvalidAddress {
+                address: Address::new(FUNCTIONS as usize + 4),
+                len: 4
+            })
+        );
+
+        let error = read_exports_of(truncated).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn corrupt_directories_are_refused() {
+        let invalid = Err(MemoryError::InvalidImage(Address::NULL));
+
+        // A name refers to a function past the end of the function table.
+        let mut file = with_exports();
+        put(&mut file, file_offset(NAME_ORDINALS + 2), &[4, 0]);
+        assert_eq!(parse_file(&file), invalid);
+
+        let mut file = with_exports();
+        put_u32(&mut file, DIRECTORY + 0x14, MAX_EXPORTS + 1);
+        assert_eq!(parse_file(&file), invalid);
+
+        let mut file = with_exports();
+        put(&mut file, 0x58, &0x107u16.to_le_bytes());
+        assert_eq!(parse_file(&file), invalid);
+
+        // The function table lies outside of every section.
+        let mut file = with_exports();
+        put_u32(&mut file, DIRECTORY + 0x1C, 0x300);
+        assert_eq!(
+            parse_file(&file),
+            Err(MemoryError::InvalidAddress {
+                address: Address::new(0x300),
+                len: 4
+            })
+        );
+
+        // A name isn't terminated before the end of the file.
+        let mut file = with_exports();
+        let end = file.len();
+        put(&mut file, end - 4, b"Name");
+        put_u32(
+            &mut file,
+            NAMES,
+            SECTION_RVA + (end - file_offset(SECTION_RVA)) as u32 - 4,
+        );
+        assert!(matches!(
+            parse_file(&file),
+            Err(MemoryError::StringTooLong { .. })
+        ));
+    }
+}
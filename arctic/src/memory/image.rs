@@ -0,0 +1,292 @@
+use super::{read_c_string, read_unaligned, Address, MemoryError, Pod, Rva};
+
+/// Offset of `e_lfanew` in the DOS header, which points to the NT headers.
+const DOS_LFANEW: u32 = 0x3C;
+
+/// Offsets in the NT headers of the number of sections, and of the optional header's size.
+const NT_SECTION_COUNT: u32 = 0x6;
+const NT_OPTIONAL_HEADER_SIZE: u32 = 0x14;
+
+/// Offset of the optional header in the NT headers.
+pub(super) const NT_OPTIONAL_HEADER: u32 = 0x18;
+
+/// Offsets of `SizeOfImage` and `SizeOfHeaders` in the NT headers, the same for 32 and 64-bit
+/// images.
+const NT_SIZE_OF_IMAGE: u32 = NT_OPTIONAL_HEADER + 0x38;
+const NT_SIZE_OF_HEADERS: u32 = NT_OPTIONAL_HEADER + 0x3C;
+
+/// Size of an entry of the section table.
+const SECTION_HEADER_SIZE: u32 = 40;
+
+/// Longest name which is read from an image.
+const MAX_NAME_LEN: usize = 1024;
+
+/// PE image which can be read by RVA, either loaded or as a file.
+pub(super) trait Image {
+    /// Reads a `T` at `rva`, regardless of its alignment.
+    fn read<T: Pod>(&self, rva: u32) -> Result<T, MemoryError>;
+
+    /// Reads the null-terminated name at `rva`.
+    fn name(&self, rva: u32) -> Result<String, MemoryError>;
+
+    /// Error for an image which doesn't look like a PE image.
+    fn invalid(&self) -> MemoryError;
+}
+
+/// Image loaded at an address, whose sections are where their RVAs say.
+pub(super) struct Loaded(pub Address);
+
+impl Image for Loaded {
+    fn read<T: Pod>(&self, rva: u32) -> Result<T, MemoryError> {
+        read_unaligned(Rva(rva).resolve_in(self.0)?)
+    }
+
+    fn name(&self, rva: u32) -> Result<String, MemoryError> {
+        read_c_string(Rva(rva).resolve_in(self.0)?, MAX_NAME_LEN)
+    }
+
+    fn invalid(&self) -> MemoryError {
+        MemoryError::InvalidImage(self.0)
+    }
+}
+
+/// Image as it's stored in a file, whose sections have to be looked up to find an RVA.
+/// Errors report RVAs as addresses.
+pub(super) struct File<'a> {
+    bytes: &'a [u8],
+
+    /// Size of the headers, which are at the same offsets in the file as once loaded.
+    headers_size: u32,
+
+    /// RVA, size in the file and file offset of every section.
+    sections: Vec<(u32, u32, u32)>,
+}
+
+impl<'a> File<'a> {
+    /// Reads the section table of the image in `bytes`.
+    pub(super) fn new(bytes: &'a [u8]) -> Result<Self, MemoryError> {
+        // Until the section table has been read, every RVA is taken to be within the headers.
+        let mut file = Self {
+            bytes,
+            headers_size: u32::MAX,
+            sections: Vec::new(),
+        };
+
+        let headers = nt_headers(&file)?;
+        let count = file.read::<u16>(headers.wrapping_add(NT_SECTION_COUNT))?;
+        let optional_header_size =
+            file.read::<u16>(headers.wrapping_add(NT_OPTIONAL_HEADER_SIZE))?;
+        let headers_size = file.read::<u32>(headers.wrapping_add(NT_SIZE_OF_HEADERS))?;
+
+        let table = headers
+            .wrapping_add(NT_OPTIONAL_HEADER)
+            .wrapping_add(u32::from(optional_header_size));
+
+        for index in 0..u32::from(count) {
+            let section = table.wrapping_add(index * SECTION_HEADER_SIZE);
+            let rva = file.read::<u32>(section.wrapping_add(0xC))?;
+            let raw_size = file.read::<u32>(section.wrapping_add(0x10))?;
+            let offset = file.read::<u32>(section.wrapping_add(0x14))?;
+            file.sections.push((rva, raw_size, offset));
+        }
+
+        file.headers_size = headers_size;
+        Ok(file)
+    }
+
+    /// The bytes of the file from `rva` onwards, which has to be followed by at least `len` bytes.
+    /// RVAs which are neither within the headers nor within a section's data in the file, such as
+    /// those of uninitialized data, are refused.
+    fn bytes(&self, rva: u32, len: usize) -> Result<&[u8], MemoryError> {
+        let offset = if rva < self.headers_size {
+            Some(rva as usize)
+        } else {
+            self.sections
+                .iter()
+                .find(|(start, size, _)| rva.wrapping_sub(*start) < *size)
+                .map(|(start, _, offset)| *offset as usize + (rva - start) as usize)
+        };
+
+        offset
+            .and_then(|offset| self.bytes.get(offset..))
+            .filter(|bytes| bytes.len() >= len)
+            .ok_or(MemoryError::InvalidAddress {
+                address: Address::new(rva as usize),
+                len,
+            })
+    }
+}
+
+impl Image for File<'_> {
+    fn read<T: Pod>(&self, rva: u32) -> Result<T, MemoryError> {
+        let bytes = self.bytes(rva, size_of::<T>())?;
+        // SAFETY: The value lies within the file, and any bytes are a valid `T`.
+        Ok(unsafe { bytes.as_ptr().cast::<T>().read_unaligned() })
+    }
+
+    fn name(&self, rva: u32) -> Result<String, MemoryError> {
+        let address = Address::new(rva as usize);
+        let bytes = self.bytes(rva, 0)?;
+        let len = bytes
+            .iter()
+            .take(MAX_NAME_LEN + 1)
+            .position(|byte| *byte == 0)
+            .ok_or(MemoryError::StringTooLong {
+                address,
+                max_len: MAX_NAME_LEN,
+            })?;
+
+        String::from_utf8(bytes[..len].to_vec()).map_err(|_| MemoryError::InvalidString(address))
+    }
+
+    fn invalid(&self) -> MemoryError {
+        MemoryError::InvalidImage(Address::NULL)
+    }
+}
+
+/// RVA of the NT headers of `image`, after checking both signatures.
+pub(super) fn nt_headers(image: &impl Image) -> Result<u32, MemoryError> {
+    if image.read::<[u8; 2]>(0)? != *b"MZ" {
+        return Err(image.invalid());
+    }
+
+    let headers = image.read::<u32>(DOS_LFANEW)?;
+    if image.read::<[u8; 4]>(headers)? != *b"PE\0\0" {
+        return Err(image.invalid());
+    }
+
+    Ok(headers)
+}
+
+/// Size in bytes of the PE image loaded at `base`, such as a module's base address on Windows.
+pub fn image_size(base: Address) -> Result<usize, MemoryError> {
+    let image = Loaded(base);
+    let headers = nt_headers(&image)?;
+    Ok(image.read::<u32>(headers.wrapping_add(NT_SIZE_OF_IMAGE))? as usize)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// RVA of the only section of `pe_file`, and where its data is in the file.
+    pub(in crate::memory) const SECTION_RVA: u32 = 0x1000;
+    const SECTION_OFFSET: usize = 0x200;
+    const SECTION_SIZE: usize = 0x200;
+
+    /// Writes `bytes` at `offset` into `file`.
+    pub(in crate::memory) fn put(file: &mut [u8], offset: usize, bytes: &[u8]) {
+        file[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// File offset of `rva`, which has to be within the section of `pe_file`.
+    pub(in crate::memory) fn file_offset(rva: u32) -> usize {
+        SECTION_OFFSET + (rva - SECTION_RVA) as usize
+    }
+
+    /// 64-bit PE file with a single section of zeros.
+    pub(in crate::memory) fn pe_file() -> Vec<u8> {
+        let mut file = vec![0; SECTION_OFFSET + SECTION_SIZE];
+        put(&mut file, 0, b"MZ");
+        put(&mut file, DOS_LFANEW as usize, &0x40u32.to_le_bytes());
+        put(&mut file, 0x40, b"PE\0\0");
+        put(&mut file, 0x46, &1u16.to_le_bytes());
+        put(&mut file, 0x54, &0xF0u16.to_le_bytes());
+        put(&mut file, 0x58, &0x20Bu16.to_le_bytes());
+        put(&mut file, 0x58 + 0x38, &0x2000u32.to_le_bytes());
+        put(
+            &mut file,
+            0x58 + 0x3C,
+            &(SECTION_OFFSET as u32).to_le_bytes(),
+        );
+
+        let section = 0x58 + 0xF0;
+        put(&mut file, section, b".edata\0\0");
+        put(
+            &mut file,
+            section + 0x8,
+            &(SECTION_SIZE as u32).to_le_bytes(),
+        );
+        put(&mut file, section + 0xC, &SECTION_RVA.to_le_bytes());
+        put(
+            &mut file,
+            section + 0x10,
+            &(SECTION_SIZE as u32).to_le_bytes(),
+        );
+        put(
+            &mut file,
+            section + 0x14,
+            &(SECTION_OFFSET as u32).to_le_bytes(),
+        );
+        file
+    }
+
+    /// `file` the way it's laid out once loaded, with its section at its RVA.
+    pub(in crate::memory) fn loaded(file: &[u8]) -> Vec<u8> {
+        let mut image = vec![0; SECTION_RVA as usize + SECTION_SIZE];
+        image[..SECTION_OFFSET].copy_from_slice(&file[..SECTION_OFFSET]);
+        image[SECTION_RVA as usize..].copy_from_slice(&file[SECTION_OFFSET..]);
+        image
+    }
+
+    #[test]
+    fn rvas_are_looked_up_in_sections() {
+        let mut bytes = pe_file();
+        put(
+            &mut bytes,
+            file_offset(SECTION_RVA + 4),
+            &7u32.to_le_bytes(),
+        );
+        let file = File::new(&bytes).unwrap();
+        assert_eq!(file.read::<u32>(SECTION_RVA + 4), Ok(7));
+        assert_eq!(file.read::<[u8; 2]>(0), Ok(*b"MZ"));
+
+        let image = loaded(&bytes);
+        assert_eq!(image_size(Address::from_ptr(image.as_ptr())), Ok(0x2000));
+        assert_eq!(
+            Loaded(Address::from_ptr(image.as_ptr())).read::<u32>(SECTION_RVA + 4),
+            Ok(7)
+        );
+    }
+
+    #[test]
+    fn rvas_outside_every_section_are_refused() {
+        let bytes = pe_file();
+        let file = File::new(&bytes).unwrap();
+
+        // Past the headers but before the section, which is within the file, but not the image.
+        let outside = SECTION_OFFSET as u32 + 0x10;
+        assert_eq!(
+            file.read::<u32>(outside),
+            Err(MemoryError::InvalidAddress {
+                address: Address::new(outside as usize),
+                len: 4
+            })
+        );
+
+        let past = SECTION_RVA + SECTION_SIZE as u32 - 2;
+        assert_eq!(
+            file.read::<u32>(past),
+            Err(MemoryError::InvalidAddress {
+                address: Address::new(past as usize),
+                len: 4
+            })
+        );
+    }
+
+    #[test]
+    fn corrupt_headers_are_refused() {
+        let bytes = pe_file();
+        assert!(matches!(
+            File::new(&bytes[..0x50]),
+            Err(MemoryError::InvalidAddress { .. })
+        ));
+
+        let mut bytes = pe_file();
+        put(&mut bytes, 0x40, b"NE\0\0");
+        assert!(matches!(
+            File::new(&bytes),
+            Err(MemoryError::InvalidImage(Address::NULL))
+        ));
+    }
+}
@@ -0,0 +1,171 @@
+use super::{exports, Address, Export, MemoryError};
+use std::path::PathBuf;
+
+/// Module loaded into the process, such as the game's executable or one of its libraries.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LoadedModule {
+    /// File name, such as `GameAssembly.dll`.
+    pub name: String,
+
+    /// Full path of the file the module was loaded from.
+    pub path: PathBuf,
+
+    /// Address the module was loaded at.
+    pub base: Address,
+
+    /// Size of the loaded module in bytes.
+    pub size: usize,
+}
+
+impl LoadedModule {
+    /// Checks whether `address` lies within the module.
+    pub fn contains(&self, address: Address) -> bool {
+        address
+            .offset_from(self.base)
+            .is_some_and(|offset| offset < self.size)
+    }
+
+    /// Every symbol the module exports, see `memory::exports`.
+    pub fn exports(&self) -> Result<Vec<Export>, MemoryError> {
+        exports(self.base)
+    }
+}
+
+/// Every module loaded into the process, in the order the OS lists them.
+#[cfg(windows)]
+pub fn modules() -> Vec<LoadedModule> {
+    use std::{ffi::OsString, os::windows::ffi::OsStringExt};
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, INVALID_HANDLE_VALUE},
+        System::Diagnostics::ToolHelp::{
+            CreateToolhelp32Snapshot, Module32FirstW, Module32NextW, MODULEENTRY32W,
+            TH32CS_SNAPMODULE, TH32CS_SNAPMODULE32,
+        },
+    };
+
+    /// Converts a null-terminated wide string from the snapshot.
+    fn wide(string: &[u16]) -> OsString {
+        let len = string.iter().position(|c| *c == 0).unwrap_or(string.len());
+        OsString::from_wide(&string[..len])
+    }
+
+    // SAFETY: A process ID of 0 snapshots the current process.
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPMODULE | TH32CS_SNAPMODULE32, 0) };
+    if snapshot == INVALID_HANDLE_VALUE {
+        return Vec::new();
+    }
+
+    let mut modules = Vec::new();
+    // SAFETY: Every field is an integer, pointer or array, so zeroes are valid.
+    let mut entry = unsafe { std::mem::zeroed::<MODULEENTRY32W>() };
+    entry.dwSize = size_of::<MODULEENTRY32W>() as u32;
+
+    // SAFETY: The snapshot is open, and `entry` has its size set.
+    let mut found = unsafe { Module32FirstW(snapshot, &mut entry) } != 0;
+    while found {
+        modules.push(LoadedModule {
+            name: wide(&entry.szModule).to_string_lossy().into_owned(),
+            path: wide(&entry.szExePath).into(),
+            base: Address::from_ptr(entry.modBaseAddr),
+            size: entry.modBaseSize as usize,
+        });
+
+        // SAFETY: As above.
+        found = unsafe { Module32NextW(snapshot, &mut entry) } != 0;
+    }
+
+    // SAFETY: The snapshot is open, and isn't used afterwards.
+    unsafe { CloseHandle(snapshot) };
+    modules
+}
+
+/// Every library mapped into the process from a file, the executable included, in ascending order.
+#[cfg(unix)]
+pub fn modules() -> Vec<LoadedModule> {
+    let Ok(maps) = std::fs::read_to_string("/proc/self/maps") else {
+        return Vec::new();
+    };
+
+    let mut modules = Vec::<LoadedModule>::new();
+    for line in maps.lines() {
+        // Such as `7f0000-7f1000 r--p 00000000 08:01 1234 /usr/lib/libgame.so`, where the mapping
+        // at offset 0 is where the library starts, and the following ones of the same file are
+        // the rest of it.
+        let mut fields = line.split_whitespace();
+        let Some((start, end)) = fields.next().and_then(|range| range.split_once('-')) else {
+            continue;
+        };
+
+        let (Some(offset), Some(path)) = (fields.nth(1), fields.nth(2)) else {
+            continue;
+        };
+
+        let (Ok(start), Ok(end), Ok(offset)) = (
+            usize::from_str_radix(start, 16),
+            usize::from_str_radix(end, 16),
+            usize::from_str_radix(offset, 16),
+        ) else {
+            continue;
+        };
+
+        if !path.starts_with('/') {
+            continue;
+        }
+
+        if offset == 0 {
+            let path = PathBuf::from(path);
+            modules.push(LoadedModule {
+                name: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                path,
+                base: Address::new(start),
+                size: end - start,
+            });
+        } else if let Some(module) = modules
+            .last_mut()
+            .filter(|module| module.path.as_os_str() == path)
+        {
+            module.size = end - module.base.get();
+        }
+    }
+
+    modules
+}
+
+/// Loaded module called `name`, such as `GameAssembly.dll`, `None` if no such module is loaded.
+/// Names are compared case-insensitively on Windows, like the OS does.
+pub fn find_module(name: &str) -> Option<LoadedModule> {
+    modules().into_iter().find(|module| {
+        if cfg!(windows) {
+            module.name.eq_ignore_ascii_case(name)
+        } else {
+            module.name == name
+        }
+    })
+}
+
+/// Base address of the loaded module called `name`, such as `GameAssembly.dll`, `None` if no
+/// such module is loaded.
+#[cfg(windows)]
+pub fn module_base(name: &str) -> Option<Address> {
+    use std::{ffi::OsStr, os::windows::ffi::OsStrExt};
+    use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+
+    let name = OsStr::new(name)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect::<Vec<_>>();
+
+    // SAFETY: The name is null-terminated, and the module's reference count isn't touched.
+    let module = unsafe { GetModuleHandleW(name.as_ptr()) };
+    (!module.is_null()).then(|| Address::from_ptr(module))
+}
+
+/// Base address of the loaded library whose file is called `name`, such as `libgame.so`, `None`
+/// if no such library is loaded.
+#[cfg(unix)]
+pub fn module_base(name: &str) -> Option<Address> {
+    find_module(name).map(|module| module.base)
+}
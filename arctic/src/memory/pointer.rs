@@ -0,0 +1,329 @@
+use super::{address::parse_hex, read, write_untracked, Address, MemoryError, ModuleAddress, Pod};
+use crate::payload;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::Path,
+    str::FromStr,
+    sync::{Mutex, PoisonError},
+};
+
+/// Multi-level pointer path, such as `GameAssembly.dll+0x1A2B30 -> 0x10 -> 0x28`: the pointer at
+/// the base is followed, the next offset added to it, and so on. The last offset is added to the
+/// last pointer without following it, so the chain resolves to the address of the value itself.
+///
+/// Resolved addresses are cached until the next tick of the payload, a null link or any other
+/// failure isn't cached.
+#[derive(Debug)]
+pub struct PointerChain {
+    base: Base,
+    offsets: Vec<isize>,
+
+    /// Tick the chain was last resolved in, and what it resolved to.
+    cache: Mutex<Option<(u64, Address)>>,
+}
+
+/// Where a pointer chain starts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Base {
+    Absolute(Address),
+    Module(ModuleAddress),
+}
+
+impl PointerChain {
+    /// Chain starting at `base`.
+    fn with_base(base: Base) -> Self {
+        Self {
+            base,
+            offsets: Vec::new(),
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// Chain starting at the absolute address `base`.
+    pub fn new(base: Address) -> Self {
+        Self::with_base(Base::Absolute(base))
+    }
+
+    /// Chain starting `offset` bytes into the module called `module`, which survives ASLR.
+    pub fn in_module(module: impl Into<String>, offset: usize) -> Self {
+        Self::with_base(Base::Module(ModuleAddress::new(module, offset)))
+    }
+
+    /// Follows the pointer so far, and adds `offset` to it.
+    pub fn with_offset(mut self, offset: isize) -> Self {
+        self.offsets.push(offset);
+        self
+    }
+
+    /// Address the chain currently leads to, cached for the rest of the tick.
+    /// Outside of ticking payloads, every call resolves the chain again.
+    pub fn resolve(&self) -> Result<Address, MemoryError> {
+        let tick = payload::ticks();
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((cached_tick, address)) = *cache {
+            if tick != 0 && cached_tick == tick {
+                return Ok(address);
+            }
+        }
+
+        let resolved = self.resolve_uncached();
+        *cache = match resolved {
+            Ok(address) if tick != 0 => Some((tick, address)),
+            _ => None,
+        };
+
+        resolved
+    }
+
+    /// Address the chain currently leads to, following every link again.
+    pub fn resolve_uncached(&self) -> Result<Address, MemoryError> {
+        let mut address = match &self.base {
+            Base::Absolute(address) => *address,
+            Base::Module(address) => address.resolve()?,
+        };
+
+        for &offset in &self.offsets {
+            let pointer = read::<Address>(address)?;
+            if pointer.is_null() {
+                return Err(MemoryError::Null);
+            }
+
+            address = pointer
+                .checked_add_signed(offset)
+                .ok_or(MemoryError::Overflow {
+                    address: pointer,
+                    len: offset.unsigned_abs(),
+                })?;
+        }
+
+        Ok(address)
+    }
+
+    /// Forgets the cached address, for when the chain is known to have changed within a tick.
+    pub fn invalidate(&self) {
+        *self.cache.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    /// Reads the value the chain leads to.
+    pub fn read<T: Pod>(&self) -> Result<T, MemoryError> {
+        read(self.resolve()?)
+    }
+
+    /// Writes the value the chain leads to, which isn't undone once the payload is ejected, see
+    /// `memory::write_untracked`.
+    pub fn write<T: Pod>(&self, value: T) -> Result<(), MemoryError> {
+        write_untracked(self.resolve()?, value)
+    }
+}
+
+impl Clone for PointerChain {
+    fn clone(&self) -> Self {
+        Self {
+            base: self.base.clone(),
+            offsets: self.offsets.clone(),
+            cache: Mutex::new(None),
+        }
+    }
+}
+
+impl PartialEq for PointerChain {
+    fn eq(&self, other: &Self) -> bool {
+        self.base == other.base && self.offsets == other.offsets
+    }
+}
+
+impl Eq for PointerChain {}
+
+/// Formats the chain the way it's parsed, such as `GameAssembly.dll+0x1A2B30 -> 0x10 -> -0x8`.
+impl Display for PointerChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.base {
+            Base::Absolute(address) => write!(f, "{address}")?,
+            Base::Module(address) => write!(f, "{address}")?,
+        }
+
+        for offset in &self.offsets {
+            let sign = if *offset < 0 { "-" } else { "" };
+            write!(f, " -> {sign}{:#x}", offset.unsigned_abs())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses chains such as `GameAssembly.dll+0x1A2B30 -> 0x10 -> -0x8`, or `0x7FF6A000 -> 0x10` for
+/// an absolute base. Every number is hexadecimal, with or without `0x`.
+impl FromStr for PointerChain {
+    type Err = InvalidPointerChain;
+
+    fn from_str(chain: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidPointerChain(chain.to_owned());
+        let mut links = chain.split("->");
+        let base = links.next().ok_or_else(invalid)?.trim();
+        let mut parsed = if base.contains('+') {
+            PointerChain::with_base(Base::Module(base.parse().map_err(|_| invalid())?))
+        } else {
+            PointerChain::new(Address::new(parse_hex(base).ok_or_else(invalid)?))
+        };
+
+        for link in links {
+            let link = link.trim();
+            let offset = match link.strip_prefix('-') {
+                Some(negative) => parse_hex(negative)
+                    .and_then(|offset| isize::try_from(offset).ok())
+                    .map(|offset| -offset),
+                None => parse_hex(link).and_then(|offset| isize::try_from(offset).ok()),
+            };
+
+            parsed = parsed.with_offset(offset.ok_or_else(invalid)?);
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// A pointer chain which couldn't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidPointerChain(pub String);
+
+impl Display for InvalidPointerChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid pointer chain {:?}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidPointerChain {}
+
+/// Reads named pointer chains from the file at `path`, one `name = chain` per line, see
+/// `PointerChain`. Empty lines and lines starting with `#` are skipped.
+pub fn load_pointer_chains(
+    path: impl AsRef<Path>,
+) -> std::io::Result<BTreeMap<String, PointerChain>> {
+    let invalid_data = |error: String| std::io::Error::new(std::io::ErrorKind::InvalidData, error);
+
+    let mut chains = BTreeMap::new();
+    for line in std::fs::read_to_string(path)?.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (name, chain) = line
+            .split_once('=')
+            .ok_or_else(|| invalid_data(format!("expected `name = chain`, got {line:?}")))?;
+
+        let chain = chain
+            .parse()
+            .map_err(|error: InvalidPointerChain| invalid_data(error.to_string()))?;
+
+        chains.insert(name.trim().to_owned(), chain);
+    }
+
+    Ok(chains)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{MockHost, MOCK_PAYLOAD, MOCK_PROCESS},
+        payload::Payload,
+    };
+
+    fn chain(chain: &str) -> PointerChain {
+        chain.parse().unwrap()
+    }
+
+    #[test]
+    fn chains_are_parsed() {
+        let health = PointerChain::in_module("GameAssembly.dll", 0x1A2B30)
+            .with_offset(0x10)
+            .with_offset(0x28);
+        assert_eq!(chain("GameAssembly.dll+0x1A2B30 -> 0x10 -> 0x28"), health);
+        assert_eq!(chain("GameAssembly.dll+1A2B30->10->28"), health);
+        assert_eq!(
+            chain("  GameAssembly.dll + 0X1a2b30\t->  0x10 ->28 "),
+            health
+        );
+        assert_eq!(chain(&health.to_string()), health);
+
+        let absolute = PointerChain::new(Address::new(0x7FF6A000)).with_offset(-0x8);
+        assert_eq!(chain("0x7FF6A000 -> -0x8"), absolute);
+        assert_eq!(chain("7ff6a000 -> -8"), absolute);
+        assert_eq!(absolute.to_string(), "0x7ff6a000 -> -0x8");
+        assert_eq!(chain("0x10"), PointerChain::new(Address::new(0x10)));
+    }
+
+    #[test]
+    fn offsets_are_hexadecimal() {
+        let decimal_looking = PointerChain::new(Address::new(0x100)).with_offset(0x10);
+        assert_eq!(chain("100 -> 10"), decimal_looking);
+    }
+
+    #[test]
+    fn malformed_chains_are_refused() {
+        for malformed in [
+            "",
+            "-> 0x10",
+            "0x10 ->",
+            "0x10 -> -> 0x8",
+            "0x10 -> +0x8",
+            "0x10 -> --0x8",
+            "0x10 -> 0x+8",
+            "0x10 -> 0xG",
+            "0x10 -> 0x8000000000000000",
+            "0x10 > 0x8",
+            "GameAssembly.dll+",
+            "+0x10",
+            "GameAssembly.dll",
+        ] {
+            assert_eq!(
+                malformed.parse::<PointerChain>(),
+                Err(InvalidPointerChain(malformed.to_owned())),
+                "{malformed:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn chains_are_followed() {
+        let value = Box::new(7u32);
+        let pointer = Box::new(Address::from_ptr(&*value).checked_sub(4).unwrap());
+        let chain = PointerChain::new(Address::from_ptr(&*pointer)).with_offset(4);
+        assert_eq!(chain.read::<u32>(), Ok(7));
+        chain.write(8u32).unwrap();
+        assert_eq!(*value, 8);
+        assert_eq!(
+            PointerChain::new(Address::NULL).with_offset(0).resolve(),
+            Err(MemoryError::Null)
+        );
+    }
+
+    #[test]
+    fn null_links_arent_cached() {
+        struct Idle;
+        impl Payload for Idle {}
+
+        let host = MockHost::new();
+        // SAFETY: The mock's table outlives the test.
+        let gateway =
+            unsafe { crate::gateway::start(MOCK_PROCESS, MOCK_PAYLOAD, host.table()) }.unwrap();
+        payload::tick(&mut Idle, gateway, 0.0);
+
+        let value = Box::new(7u32);
+        let mut pointer = Box::new(Address::NULL);
+        let chain = PointerChain::new(Address::from_ptr(&*pointer)).with_offset(0);
+        assert_eq!(chain.resolve(), Err(MemoryError::Null));
+
+        // The link is only set later in the same tick, which is picked up right away.
+        *pointer = Address::from_ptr(&*value);
+        assert_eq!(chain.read::<u32>(), Ok(7));
+
+        // Resolved addresses are cached for the rest of the tick.
+        *pointer = Address::NULL;
+        assert_eq!(chain.resolve(), Ok(Address::from_ptr(&*value)));
+        payload::tick(&mut Idle, gateway, 0.0);
+        assert_eq!(chain.resolve(), Err(MemoryError::Null));
+        assert_eq!(chain.read::<u32>(), Err(MemoryError::Null));
+    }
+}
@@ -0,0 +1,147 @@
+use super::{query, Address, MemoryError, Protection};
+
+/// Protection of a range before it was made writable, in the platform's own terms.
+#[cfg(unix)]
+type Native = Protection;
+#[cfg(windows)]
+type Native = windows_sys::Win32::System::Memory::PAGE_PROTECTION_FLAGS;
+
+/// Ranges which have been made readable and writable, their previous protection is restored once
+/// this is dropped.
+pub(crate) struct Unprotected(Vec<(Address, usize, Native)>);
+
+impl Drop for Unprotected {
+    fn drop(&mut self) {
+        for &(start, len, previous) in self.0.iter().rev() {
+            restore(start, len, previous);
+        }
+    }
+}
+
+/// Makes every byte of the `len` bytes at `address` readable and writable until the returned guard
+/// is dropped, keeping pages executable if they were.
+pub(crate) fn unprotect(address: Address, len: usize) -> Result<Unprotected, MemoryError> {
+    let invalid = || MemoryError::InvalidAddress { address, len };
+    let end = address
+        .checked_add(len)
+        .ok_or(MemoryError::Overflow { address, len })?;
+
+    let mut unprotected = Unprotected(Vec::new());
+    let mut cursor = address;
+    while cursor < end {
+        let region = query(cursor).ok_or_else(invalid)?;
+        let part = region.end.min(end).get() - cursor.get();
+        if !region.protection.read || !region.protection.write {
+            let previous = make_writable(cursor, part, region.protection).ok_or_else(invalid)?;
+            unprotected.0.push((cursor, part, previous));
+        }
+
+        cursor = region.end;
+    }
+
+    Ok(unprotected)
+}
+
+/// Pages covering the `len` bytes at `start`, since protection can only be changed per page.
+#[cfg(unix)]
+fn pages(start: Address, len: usize) -> (*mut libc::c_void, usize) {
+    // SAFETY: `sysconf` has no preconditions.
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let first = start.get() & !(page_size - 1);
+    let end = (start.get() + len).next_multiple_of(page_size);
+    (first as *mut libc::c_void, end - first)
+}
+
+#[cfg(unix)]
+fn flags(protection: Protection) -> libc::c_int {
+    let mut flags = libc::PROT_NONE;
+    if protection.read {
+        flags |= libc::PROT_READ;
+    }
+
+    if protection.write {
+        flags |= libc::PROT_WRITE;
+    }
+
+    if protection.execute {
+        flags |= libc::PROT_EXEC;
+    }
+
+    flags
+}
+
+#[cfg(unix)]
+fn make_writable(start: Address, len: usize, protection: Protection) -> Option<Native> {
+    let (first, size) = pages(start, len);
+    let writable = Protection {
+        read: true,
+        write: true,
+        ..protection
+    };
+
+    // SAFETY: The pages are mapped, and only gain permissions.
+    (unsafe { libc::mprotect(first, size, flags(writable)) } == 0).then_some(protection)
+}
+
+#[cfg(unix)]
+fn restore(start: Address, len: usize, previous: Native) {
+    let (first, size) = pages(start, len);
+    // SAFETY: The pages are mapped, and get back the protection they had before.
+    unsafe { libc::mprotect(first, size, flags(previous)) };
+}
+
+#[cfg(windows)]
+fn make_writable(start: Address, len: usize, protection: Protection) -> Option<Native> {
+    use windows_sys::Win32::System::Memory::{
+        VirtualProtect, PAGE_EXECUTE_READWRITE, PAGE_READWRITE,
+    };
+
+    let writable = if protection.execute {
+        PAGE_EXECUTE_READWRITE
+    } else {
+        PAGE_READWRITE
+    };
+
+    let mut previous = 0;
+    // SAFETY: The range is committed, and only gains permissions.
+    (unsafe { VirtualProtect(start.as_ptr(), len, writable, &mut previous) } != 0)
+        .then_some(previous)
+}
+
+#[cfg(windows)]
+fn restore(start: Address, len: usize, previous: Native) {
+    use windows_sys::Win32::System::Memory::VirtualProtect;
+
+    let mut unused = 0;
+    // SAFETY: The range is committed, and gets back the protection it had before.
+    unsafe { VirtualProtect(start.as_ptr(), len, previous, &mut unused) };
+}
+
+/// Makes sure the processor picks up code which has been written to the `len` bytes at `start`.
+#[cfg(windows)]
+pub(crate) fn flush_instruction_cache(start: Address, len: usize) {
+    use windows_sys::Win32::System::{
+        Diagnostics::Debug::FlushInstructionCache, Threading::GetCurrentProcess,
+    };
+
+    // SAFETY: Flushing has no effect on the contents of memory.
+    unsafe { FlushInstructionCache(GetCurrentProcess(), start.as_ptr(), len) };
+}
+
+/// Makes sure the processor picks up code which has been written to the `len` bytes at `start`.
+/// Nothing to do on x86, which keeps its instruction cache coherent on its own.
+#[cfg(all(unix, any(target_arch = "x86", target_arch = "x86_64")))]
+pub(crate) fn flush_instruction_cache(_start: Address, _len: usize) {}
+
+/// Makes sure the processor picks up code which has been written to the `len` bytes at `start`.
+/// Other architectures, such as ARM, have to be told through the compiler runtime.
+#[cfg(all(unix, not(any(target_arch = "x86", target_arch = "x86_64"))))]
+pub(crate) fn flush_instruction_cache(start: Address, len: usize) {
+    extern "C" {
+        fn __clear_cache(start: *mut std::ffi::c_char, end: *mut std::ffi::c_char);
+    }
+
+    let start = start.as_ptr::<std::ffi::c_char>().cast_mut();
+    // SAFETY: Clearing the cache has no effect on the contents of memory.
+    unsafe { __clear_cache(start, start.wrapping_add(len)) };
+}
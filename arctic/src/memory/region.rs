@@ -0,0 +1,454 @@
+use super::{Address, MemoryError};
+
+/// What a region of memory may be accessed for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Protection {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+/// Range of mapped memory which shares the same protection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Region {
+    /// First address of the region.
+    pub start: Address,
+
+    /// Address right after the region.
+    pub end: Address,
+
+    pub protection: Protection,
+}
+
+impl Region {
+    /// Size of the region in bytes.
+    pub const fn len(&self) -> usize {
+        self.end.get() - self.start.get()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Checks whether `address` lies within the region.
+    pub fn contains(&self, address: Address) -> bool {
+        self.start <= address && address < self.end
+    }
+}
+
+/// Every mapped region of the process, in ascending order.
+#[cfg(unix)]
+pub fn regions() -> Vec<Region> {
+    std::fs::read_to_string("/proc/self/maps")
+        .map(|maps| maps.lines().filter_map(parse_maps_line).collect())
+        .unwrap_or_default()
+}
+
+/// Parses a line of `/proc/self/maps`, such as `7f0000-7f1000 r-xp 00000000 08:01 1234 /lib.so`.
+#[cfg(unix)]
+fn parse_maps_line(line: &str) -> Option<Region> {
+    let mut fields = line.split_whitespace();
+    let (start, end) = fields.next()?.split_once('-')?;
+    let permissions = fields.next()?.as_bytes();
+    Some(Region {
+        start: Address::new(usize::from_str_radix(start, 16).ok()?),
+        end: Address::new(usize::from_str_radix(end, 16).ok()?),
+        protection: Protection {
+            read: permissions.first() == Some(&b'r'),
+            write: permissions.get(1) == Some(&b'w'),
+            execute: permissions.get(2) == Some(&b'x'),
+        },
+    })
+}
+
+/// Region containing `address`, `None` if it isn't mapped.
+#[cfg(unix)]
+pub fn query(address: Address) -> Option<Region> {
+    let maps = std::fs::read_to_string("/proc/self/maps").ok()?;
+    maps.lines()
+        .filter_map(parse_maps_line)
+        .find(|region| region.contains(address))
+}
+
+/// Every committed region of the process, in ascending order.
+#[cfg(windows)]
+pub fn regions() -> Vec<Region> {
+    let mut regions = Vec::new();
+    let mut address = Address::NULL;
+    while let Some((region, committed)) = virtual_query(address) {
+        if committed {
+            regions.push(region);
+        }
+
+        if region.end <= address {
+            break;
+        }
+
+        address = region.end;
+    }
+
+    regions
+}
+
+/// Region containing `address`, `None` if it isn't committed.
+#[cfg(windows)]
+pub fn query(address: Address) -> Option<Region> {
+    virtual_query(address)
+        .filter(|(_, committed)| *committed)
+        .map(|(region, _)| region)
+}
+
+/// Region containing `address` as reported by `VirtualQuery`, along with whether it's committed.
+#[cfg(windows)]
+fn virtual_query(address: Address) -> Option<(Region, bool)> {
+    use windows_sys::Win32::System::Memory::{
+        VirtualQuery, MEMORY_BASIC_INFORMATION, MEM_COMMIT, PAGE_EXECUTE, PAGE_EXECUTE_READ,
+        PAGE_EXECUTE_READWRITE, PAGE_EXECUTE_WRITECOPY, PAGE_GUARD, PAGE_NOACCESS, PAGE_READONLY,
+        PAGE_READWRITE, PAGE_WRITECOPY,
+    };
+
+    let mut info = std::mem::MaybeUninit::<MEMORY_BASIC_INFORMATION>::zeroed();
+    // SAFETY: `VirtualQuery` only fills in `info`, and accepts any address.
+    let written = unsafe {
+        VirtualQuery(
+            address.as_ptr(),
+            info.as_mut_ptr(),
+            std::mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+        )
+    };
+
+    if written == 0 {
+        return None;
+    }
+
+    // SAFETY: `VirtualQuery` succeeded, so `info` has been filled in.
+    let info = unsafe { info.assume_init() };
+    let start = Address::from_ptr(info.BaseAddress);
+    let protect = info.Protect;
+    let accessible = protect & (PAGE_GUARD | PAGE_NOACCESS) == 0;
+    let has = |flags: u32| accessible && protect & flags != 0;
+    let region = Region {
+        start,
+        end: start
+            .checked_add(info.RegionSize)
+            .unwrap_or(Address::new(usize::MAX)),
+        protection: Protection {
+            read: has(PAGE_READONLY
+                | PAGE_READWRITE
+                | PAGE_WRITECOPY
+                | PAGE_EXECUTE_READ
+                | PAGE_EXECUTE_READWRITE
+                | PAGE_EXECUTE_WRITECOPY),
+            write: has(PAGE_READWRITE
+                | PAGE_WRITECOPY
+                | PAGE_EXECUTE_READWRITE
+                | PAGE_EXECUTE_WRITECOPY),
+            execute: has(PAGE_EXECUTE
+                | PAGE_EXECUTE_READ
+                | PAGE_EXECUTE_READWRITE
+                | PAGE_EXECUTE_WRITECOPY),
+        },
+    };
+
+    Some((region, info.State == MEM_COMMIT))
+}
+
+/// Copies the bytes at `address` into `buffer`, reporting bytes which aren't mapped or readable as
+/// `InvalidAddress` rather than crashing, even if they're unmapped while they're being copied.
+#[cfg(unix)]
+pub(crate) fn read_into(address: Address, buffer: &mut [u8]) -> Result<(), MemoryError> {
+    let len = buffer.len();
+    check_range(address, len)?;
+    let local = libc::iovec {
+        iov_base: buffer.as_mut_ptr().cast(),
+        iov_len: len,
+    };
+
+    let remote = libc::iovec {
+        iov_base: address.as_mut_ptr(),
+        iov_len: len,
+    };
+
+    // SAFETY: `buffer` is valid for writes, and the kernel checks the bytes at `address` itself,
+    // failing rather than faulting if they aren't readable.
+    let copied = unsafe { libc::process_vm_readv(libc::getpid(), &local, 1, &remote, 1, 0) };
+    match copied_all(copied, len) {
+        Some(true) => Ok(()),
+        Some(false) => Err(MemoryError::InvalidAddress { address, len }),
+        None => {
+            check(address, len, |protection| protection.read)?;
+            // SAFETY: The bytes have just been checked to be readable, and `copy` allows them to
+            // overlap with `buffer`.
+            unsafe { std::ptr::copy(address.as_ptr(), buffer.as_mut_ptr(), len) };
+            Ok(())
+        }
+    }
+}
+
+/// Copies `bytes` to `address`, reporting bytes which aren't mapped or writable as
+/// `InvalidAddress` rather than crashing, even if they're unmapped while they're being copied.
+/// Some of the bytes may have been copied when this fails.
+#[cfg(unix)]
+pub(crate) fn write_from(address: Address, bytes: &[u8]) -> Result<(), MemoryError> {
+    let len = bytes.len();
+    check_range(address, len)?;
+    let local = libc::iovec {
+        iov_base: bytes.as_ptr().cast_mut().cast(),
+        iov_len: len,
+    };
+
+    let remote = libc::iovec {
+        iov_base: address.as_mut_ptr(),
+        iov_len: len,
+    };
+
+    // SAFETY: `bytes` is valid for reads, and the kernel checks the bytes at `address` itself,
+    // failing rather than faulting if they aren't writable.
+    let copied = unsafe { libc::process_vm_writev(libc::getpid(), &local, 1, &remote, 1, 0) };
+    match copied_all(copied, len) {
+        Some(true) => Ok(()),
+        Some(false) => Err(MemoryError::InvalidAddress { address, len }),
+        None => {
+            check(address, len, |protection| protection.write)?;
+            // SAFETY: The bytes have just been checked to be writable, and `copy` allows them to
+            // overlap with `bytes`.
+            unsafe { std::ptr::copy(bytes.as_ptr(), address.as_mut_ptr(), len) };
+            Ok(())
+        }
+    }
+}
+
+/// Checks whether `process_vm_readv` or `process_vm_writev` copied all `len` bytes, `None` if the
+/// system call isn't available, such as when a sandbox filters it, and the memory map has to be
+/// checked instead.
+#[cfg(unix)]
+fn copied_all(copied: isize, len: usize) -> Option<bool> {
+    if copied >= 0 {
+        return Some(copied as usize == len);
+    }
+
+    let error = std::io::Error::last_os_error().raw_os_error();
+    (!matches!(error, Some(libc::ENOSYS | libc::EPERM))).then_some(false)
+}
+
+/// Copies the bytes at `address` into `buffer`, reporting bytes which aren't committed or readable
+/// as `InvalidAddress` rather than crashing, even if they're freed while they're being copied.
+#[cfg(windows)]
+pub(crate) fn read_into(address: Address, buffer: &mut [u8]) -> Result<(), MemoryError> {
+    use windows_sys::Win32::System::{
+        Diagnostics::Debug::ReadProcessMemory, Threading::GetCurrentProcess,
+    };
+
+    let len = buffer.len();
+    check_range(address, len)?;
+    if len == 0 {
+        return Ok(());
+    }
+
+    let mut copied = 0;
+    // SAFETY: `buffer` is valid for writes, and `ReadProcessMemory` checks the bytes at `address`
+    // itself, failing rather than faulting if they aren't readable.
+    let read = unsafe {
+        ReadProcessMemory(
+            GetCurrentProcess(),
+            address.as_ptr(),
+            buffer.as_mut_ptr().cast(),
+            len,
+            &mut copied,
+        )
+    };
+
+    if read == 0 || copied != len {
+        return Err(MemoryError::InvalidAddress { address, len });
+    }
+
+    Ok(())
+}
+
+/// Copies `bytes` to `address`, reporting bytes which aren't committed or writable as
+/// `InvalidAddress` rather than crashing, even if they're freed while they're being copied.
+/// Some of the bytes may have been copied when this fails.
+#[cfg(windows)]
+pub(crate) fn write_from(address: Address, bytes: &[u8]) -> Result<(), MemoryError> {
+    use windows_sys::Win32::System::{
+        Diagnostics::Debug::WriteProcessMemory, Threading::GetCurrentProcess,
+    };
+
+    let len = bytes.len();
+    check_range(address, len)?;
+    if len == 0 {
+        return Ok(());
+    }
+
+    let mut copied = 0;
+    // SAFETY: `bytes` is valid for reads, and `WriteProcessMemory` checks the bytes at `address`
+    // itself, failing rather than faulting if they aren't writable.
+    let written = unsafe {
+        WriteProcessMemory(
+            GetCurrentProcess(),
+            address.as_ptr(),
+            bytes.as_ptr().cast(),
+            len,
+            &mut copied,
+        )
+    };
+
+    if written == 0 || copied != len {
+        return Err(MemoryError::InvalidAddress { address, len });
+    }
+
+    Ok(())
+}
+
+/// Checks that the `len` bytes at `address` don't wrap around the address space.
+fn check_range(address: Address, len: usize) -> Result<(), MemoryError> {
+    address
+        .checked_add(len)
+        .map(drop)
+        .ok_or(MemoryError::Overflow { address, len })
+}
+
+/// Checks that every byte of the `len` bytes at `address` is mapped with a protection `allows`.
+pub(crate) fn check(
+    address: Address,
+    len: usize,
+    allows: impl Fn(Protection) -> bool,
+) -> Result<(), MemoryError> {
+    let end = address
+        .checked_add(len)
+        .ok_or(MemoryError::Overflow { address, len })?;
+
+    let mut cursor = address;
+    while cursor < end {
+        match query(cursor) {
+            Some(region) if allows(region.protection) => cursor = region.end,
+            _ => return Err(MemoryError::InvalidAddress { address, len }),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::memory::{read, read_bytes_into, read_slice, write, READ_CHUNK_SIZE};
+
+    /// Pages of anonymous memory, holding `7` in their first four bytes, which are unmapped once
+    /// dropped.
+    struct Pages(Address, usize);
+
+    impl Pages {
+        fn new(count: usize) -> Self {
+            let len = count * page_size();
+            // SAFETY: Mapping fresh anonymous memory doesn't affect any other memory.
+            let pages = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                    -1,
+                    0,
+                )
+            };
+
+            assert_ne!(pages, libc::MAP_FAILED);
+            // SAFETY: The pages have just been mapped readable and writable.
+            unsafe { pages.cast::<u32>().write(7) };
+            Self(Address::from_ptr(pages), len)
+        }
+
+        /// Changes the protection of the `index`th page.
+        fn protect(&self, index: usize, flags: libc::c_int) {
+            let page = self.0.get() + index * page_size();
+            // SAFETY: The pages are owned by this test.
+            let result = unsafe { libc::mprotect(page as *mut libc::c_void, page_size(), flags) };
+            assert_eq!(result, 0);
+        }
+
+        fn unmap(self) -> Address {
+            let address = self.0;
+            drop(self);
+            address
+        }
+    }
+
+    impl Drop for Pages {
+        fn drop(&mut self) {
+            // SAFETY: The pages are owned by this test, and nothing refers to them anymore.
+            unsafe { libc::munmap(self.0.as_mut_ptr(), self.1) };
+        }
+    }
+
+    fn page_size() -> usize {
+        // SAFETY: `sysconf` has no preconditions.
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+
+    #[test]
+    fn mapped_pages_are_read() {
+        let pages = Pages::new(1);
+        assert_eq!(read::<u32>(pages.0), Ok(7));
+        assert_eq!(
+            query(pages.0).map(|region| region.protection.write),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn unmapped_pages_are_invalid() {
+        let address = Pages::new(1).unmap();
+        let invalid = MemoryError::InvalidAddress { address, len: 4 };
+        assert_eq!(read::<u32>(address), Err(invalid.clone()));
+        assert_eq!(write::<u32>(address, 1), Err(invalid));
+        assert_eq!(query(address), None);
+    }
+
+    #[test]
+    fn protected_pages_are_invalid() {
+        let pages = Pages::new(1);
+        pages.protect(0, libc::PROT_NONE);
+        let invalid = MemoryError::InvalidAddress {
+            address: pages.0,
+            len: 4,
+        };
+
+        assert_eq!(read::<u32>(pages.0), Err(invalid.clone()));
+        assert_eq!(
+            check(pages.0, 4, |protection| protection.read),
+            Err(invalid)
+        );
+        assert_eq!(
+            read_slice::<u8>(pages.0, 2 * READ_CHUNK_SIZE),
+            Err(MemoryError::InvalidAddress {
+                address: pages.0,
+                len: 2 * READ_CHUNK_SIZE
+            })
+        );
+    }
+
+    #[test]
+    fn reads_stop_at_protected_pages() {
+        let pages = Pages::new(2);
+        let address = Address::new(pages.0.get() + page_size() - 2);
+        let mut buffer = [0; 4];
+        assert_eq!(read_bytes_into(address, &mut buffer), Ok(()));
+
+        pages.protect(1, libc::PROT_NONE);
+        assert_eq!(
+            read_bytes_into(address, &mut buffer),
+            Err(MemoryError::InvalidAddress { address, len: 4 })
+        );
+        assert_eq!(read_bytes_into(address, &mut buffer[..2]), Ok(()));
+    }
+
+    #[test]
+    fn ranges_wrapping_around_are_refused() {
+        let address = Address::new(usize::MAX - 1);
+        assert_eq!(
+            read_into(address, &mut [0; 4]),
+            Err(MemoryError::Overflow { address, len: 4 })
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use super::{read, read_c_string, write_untracked, Address, MemoryError, Pod};
+use std::marker::PhantomData;
+
+/// Longest `String` field which can be read.
+pub const MAX_STRING_LEN: usize = 1024;
+
+/// Type a field of a `remote_struct!` can have.
+pub trait FieldValue: Sized {
+    /// Reads the field at `address`.
+    fn read_field(address: Address) -> Result<Self, MemoryError>;
+}
+
+/// Type of a field of a `remote_struct!` which can be written as well.
+pub trait FieldValueMut: FieldValue {
+    /// Writes the field at `address`, see `memory::write_untracked`.
+    fn write_field(address: Address, value: Self) -> Result<(), MemoryError>;
+}
+
+impl<T: Pod> FieldValue for T {
+    fn read_field(address: Address) -> Result<Self, MemoryError> {
+        read(address)
+    }
+}
+
+impl<T: Pod> FieldValueMut for T {
+    fn write_field(address: Address, value: Self) -> Result<(), MemoryError> {
+        write_untracked(address, value)
+    }
+}
+
+/// Strings are read as null-terminated UTF-8 of at most `MAX_STRING_LEN` bytes, other layouts can
+/// be read from a field's address through `memory::read_msvc_string` and the like.
+impl FieldValue for String {
+    fn read_field(address: Address) -> Result<Self, MemoryError> {
+        read_c_string(address, MAX_STRING_LEN)
+    }
+}
+
+/// Field `offset` bytes into a struct in the game's memory, see `remote_struct!`.
+pub struct Field<T> {
+    base: Address,
+    offset: usize,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T: FieldValue> Field<T> {
+    /// Field `offset` bytes into the struct at `base`.
+    pub const fn new(base: Address, offset: usize) -> Self {
+        Self {
+            base,
+            offset,
+            value: PhantomData,
+        }
+    }
+
+    /// Address of the field.
+    pub fn address(&self) -> Result<Address, MemoryError> {
+        if self.base.is_null() {
+            return Err(MemoryError::Null);
+        }
+
+        self.base
+            .checked_add(self.offset)
+            .ok_or(MemoryError::Overflow {
+                address: self.base,
+                len: self.offset,
+            })
+    }
+
+    /// Reads the field.
+    pub fn get(&self) -> Result<T, MemoryError> {
+        T::read_field(self.address()?)
+    }
+}
+
+impl<T: FieldValueMut> Field<T> {
+    /// Writes the field, which isn't undone once the payload is ejected since the struct may have
+    /// been freed by then, see `memory::write_untracked`.
+    pub fn set(&self, value: T) -> Result<(), MemoryError> {
+        T::write_field(self.address()?, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::{revert, Address, MemoryError};
+
+    remote_struct! {
+        struct Weapon {
+            0x4 => ammo: u32,
+        }
+    }
+
+    remote_struct! {
+        struct Player {
+            0x0 => health: f32,
+            0x8 => weapon: Weapon,
+        }
+    }
+
+    #[test]
+    fn fields_are_written_untracked() {
+        let weapon = Box::new([0u32, 30]);
+        let mut player = Box::new([0usize; 2]);
+        player[1] = weapon.as_ptr() as usize;
+        let player = Player::new(Address::from_ptr(player.as_ptr()));
+
+        player.health().set(100.0).unwrap();
+        assert_eq!(player.health().get(), Ok(100.0));
+        assert_eq!(revert(player.health().address().unwrap()), Ok(false));
+
+        let weapon = player.weapon().get().unwrap();
+        weapon.ammo().set(29).unwrap();
+        assert_eq!(weapon.ammo().get(), Ok(29));
+
+        player.weapon().set(Weapon::new(Address::NULL)).unwrap();
+        assert_eq!(player.weapon().get(), Err(MemoryError::Null));
+        assert_eq!(revert(player.address()), Ok(false));
+    }
+}
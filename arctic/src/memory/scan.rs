@@ -0,0 +1,328 @@
+use super::{image_size, read_unaligned, regions, Address, MemoryError};
+use memchr::memmem::Finder;
+use std::{fmt::Display, str::FromStr};
+
+/// Byte signature with wildcards, parsed from IDA-style patterns such as `48 8B ?? ?? E8`, where
+/// `?` and `??` match any byte.
+/// Candidates are found by searching for the longest run of fixed bytes with SIMD, and only then
+/// compared byte by byte.
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    /// Bytes to match, `None` for wildcards.
+    bytes: Vec<Option<u8>>,
+
+    /// Offset of the longest run of fixed bytes, and a finder for it.
+    anchor: usize,
+    finder: Finder<'static>,
+}
+
+impl Pattern {
+    /// Builds a pattern from bytes to match, `None` for wildcards.
+    /// Patterns without any fixed bytes are refused, since they'd match anywhere.
+    pub fn new(bytes: Vec<Option<u8>>) -> Result<Self, InvalidPattern> {
+        if bytes.iter().all(Option::is_none) {
+            let pattern = bytes.iter().map(|_| "??").collect::<Vec<_>>().join(" ");
+            return Err(InvalidPattern(pattern));
+        }
+
+        let (mut anchor, mut anchor_len, mut run) = (0, 0, 0);
+        for (index, byte) in bytes.iter().enumerate() {
+            run = if byte.is_some() { run + 1 } else { 0 };
+            if run > anchor_len {
+                (anchor, anchor_len) = (index + 1 - run, run);
+            }
+        }
+
+        let fixed = bytes[anchor..anchor + anchor_len]
+            .iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+
+        Ok(Self {
+            finder: Finder::new(&fixed).into_owned(),
+            anchor,
+            bytes,
+        })
+    }
+
+    /// Length of the pattern in bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Checks whether `haystack` starts with the pattern.
+    pub fn matches(&self, haystack: &[u8]) -> bool {
+        haystack.len() >= self.len()
+            && self
+                .bytes
+                .iter()
+                .zip(haystack)
+                .all(|(expected, byte)| expected.is_none_or(|expected| expected == *byte))
+    }
+
+    /// Offsets of every match in `haystack`, in ascending order.
+    pub fn find_iter<'a>(&'a self, haystack: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        // Candidates may overlap, so the search resumes right after the previous one.
+        let mut position = 0;
+        std::iter::from_fn(move || {
+            let found = position + self.finder.find(haystack.get(position..)?)?;
+            position = found + 1;
+            Some(found)
+        })
+        .filter_map(|found| found.checked_sub(self.anchor))
+        .filter(|&start| self.matches(&haystack[start..]))
+    }
+
+    /// Offset of the first match in `haystack`.
+    pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+        self.find_iter(haystack).next()
+    }
+
+    /// Calls `found` with every match in the readable parts of the `len` bytes at `start`, until it
+    /// returns `false`.
+    fn scan_with(
+        &self,
+        start: Address,
+        len: usize,
+        mut found: impl FnMut(Address) -> bool,
+    ) -> Result<(), MemoryError> {
+        if start.is_null() {
+            return Err(MemoryError::Null);
+        }
+
+        let end = start.checked_add(len).ok_or(MemoryError::Overflow {
+            address: start,
+            len,
+        })?;
+
+        // Adjacent readable regions are merged, so that matches can straddle them.
+        let mut spans = Vec::<(Address, Address)>::new();
+        for region in regions() {
+            let (from, to) = (region.start.max(start), region.end.min(end));
+            if !region.protection.read || from >= to {
+                continue;
+            }
+
+            match spans.last_mut() {
+                Some((_, span_end)) if *span_end == from => *span_end = to,
+                _ => spans.push((from, to)),
+            }
+        }
+
+        if spans.is_empty() {
+            return Err(MemoryError::InvalidAddress {
+                address: start,
+                len,
+            });
+        }
+
+        for (from, to) in spans {
+            // SAFETY: The span is readable. Its contents may change while it's scanned, which only
+            // affects what's found.
+            let haystack =
+                unsafe { std::slice::from_raw_parts(from.as_ptr::<u8>(), to.get() - from.get()) };
+
+            for offset in self.find_iter(haystack) {
+                if !found(Address::new(from.get() + offset)) {
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Address of the first match in the readable parts of the `len` bytes at `start`.
+    pub fn scan(&self, start: Address, len: usize) -> Result<Option<Address>, MemoryError> {
+        let mut first = None;
+        self.scan_with(start, len, |address| {
+            first = Some(address);
+            false
+        })?;
+
+        Ok(first)
+    }
+
+    /// Addresses of every match in the readable parts of the `len` bytes at `start`.
+    pub fn scan_all(&self, start: Address, len: usize) -> Result<Vec<Address>, MemoryError> {
+        let mut all = Vec::new();
+        self.scan_with(start, len, |address| {
+            all.push(address);
+            true
+        })?;
+
+        Ok(all)
+    }
+
+    /// Address of the first match in the PE image loaded at `base`, such as a module's base
+    /// address.
+    pub fn scan_image(&self, base: Address) -> Result<Option<Address>, MemoryError> {
+        self.scan(base, image_size(base)?)
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = InvalidPattern;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidPattern(pattern.to_owned());
+        let mut bytes = Vec::new();
+        for token in pattern.split_whitespace() {
+            match token {
+                "?" | "??" => bytes.push(None),
+                // `from_str_radix` would accept a sign, such as `+1`.
+                byte if byte.len() == 2 && byte.bytes().all(|digit| digit.is_ascii_hexdigit()) => {
+                    bytes.push(Some(u8::from_str_radix(byte, 16).map_err(|_| invalid())?))
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Self::new(bytes).map_err(|_| invalid())
+    }
+}
+
+/// A pattern which has been refused, since it has tokens other than hex bytes and wildcards, or no
+/// fixed bytes at all.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidPattern(pub String);
+
+impl Display for InvalidPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid byte pattern {:?}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidPattern {}
+
+/// Resolves a RIP-relative operand of the instruction at `instruction`, such as the target of
+/// `E8 rel32` (`displacement_offset` 1, `instruction_len` 5) or the address loaded by
+/// `48 8B 05 rel32` (3 and 7).
+pub fn resolve_relative(
+    instruction: Address,
+    displacement_offset: usize,
+    instruction_len: usize,
+) -> Result<Address, MemoryError> {
+    let overflow = || MemoryError::Overflow {
+        address: instruction,
+        len: instruction_len,
+    };
+
+    let displacement = instruction
+        .checked_add(displacement_offset)
+        .ok_or_else(overflow)?;
+
+    let displacement = read_unaligned::<i32>(displacement)?;
+    instruction
+        .checked_add(instruction_len)
+        .and_then(|next| next.checked_add_signed(displacement as isize))
+        .ok_or_else(overflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(pattern: &str) -> Pattern {
+        pattern.parse().unwrap()
+    }
+
+    #[test]
+    fn patterns_are_parsed() {
+        assert_eq!(
+            pattern("48 8b ?? ? E8").bytes,
+            [Some(0x48), Some(0x8B), None, None, Some(0xE8)]
+        );
+        assert_eq!(pattern("  ?? 00\t ff ").bytes, [None, Some(0), Some(0xFF)]);
+    }
+
+    #[test]
+    fn malformed_patterns_are_refused() {
+        for malformed in [
+            "", "   ", "?? ?", "+1", "48 -1", "4", "48 8", "4G", "0x48", "48 ???", "488B",
+        ] {
+            assert_eq!(
+                malformed.parse::<Pattern>().err(),
+                Some(InvalidPattern(malformed.to_owned())),
+                "{malformed:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn patterns_without_fixed_bytes_are_refused() {
+        assert_eq!(
+            Pattern::new(Vec::new()).err(),
+            Some(InvalidPattern(String::new()))
+        );
+        assert_eq!(
+            Pattern::new(vec![None, None]).err(),
+            Some(InvalidPattern("?? ??".to_owned()))
+        );
+        assert_eq!(Pattern::new(vec![None, Some(0xE8)]).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn matches_are_found_anywhere() {
+        let haystack = [0xE8, 1, 2, 0xAA, 0xAA, 0xAA, 0xE8, 3];
+        assert_eq!(
+            pattern("E8 ?? 02").find_iter(&haystack).collect::<Vec<_>>(),
+            [0]
+        );
+        assert_eq!(
+            pattern("?? E8 03").find_iter(&haystack).collect::<Vec<_>>(),
+            [5]
+        );
+        assert_eq!(
+            pattern("AA AA").find_iter(&haystack).collect::<Vec<_>>(),
+            [3, 4]
+        );
+        assert_eq!(
+            pattern("E8 ??").find_iter(&haystack).collect::<Vec<_>>(),
+            [0, 6]
+        );
+        assert_eq!(pattern("03 ??").find(&haystack), None);
+        assert_eq!(pattern("?? ?? E8").find(&haystack), Some(4));
+    }
+
+    #[test]
+    fn memory_is_scanned() {
+        let haystack = Box::new([0x90u8, 0x48, 0x8B, 0x05, 0x10, 0, 0, 0, 0x48, 0x8B, 0x05]);
+        let start = Address::from_ptr(haystack.as_ptr());
+        let at = |offset| start.checked_add(offset).unwrap();
+        let found = pattern("48 8B 05").scan_all(start, haystack.len());
+        assert_eq!(found, Ok(vec![at(1), at(8)]));
+        assert_eq!(
+            pattern("48 8B 05 ?? ?? ?? ??").scan(start, haystack.len()),
+            Ok(Some(at(1)))
+        );
+        assert_eq!(pattern("CC").scan(start, haystack.len()), Ok(None));
+        assert_eq!(pattern("CC").scan(Address::NULL, 1), Err(MemoryError::Null));
+    }
+
+    #[test]
+    fn relative_operands_are_resolved() {
+        let code = Box::new([
+            0xE8u8, 0x10, 0, 0, 0, 0x48, 0x8B, 0x05, 0xF0, 0xFF, 0xFF, 0xFF,
+        ]);
+        let start = Address::from_ptr(code.as_ptr());
+        let at = |offset| start.checked_add(offset).unwrap();
+        assert_eq!(resolve_relative(start, 1, 5), Ok(at(5 + 0x10)));
+        assert_eq!(
+            resolve_relative(at(5), 3, 7),
+            Ok(at(12).checked_sub(0x10).unwrap())
+        );
+        assert_eq!(
+            resolve_relative(Address::NULL, 1, 5),
+            Err(MemoryError::InvalidAddress {
+                address: Address::new(1),
+                len: 4
+            })
+        );
+    }
+}
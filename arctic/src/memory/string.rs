@@ -0,0 +1,324 @@
+use super::{read, read_slice, read_unaligned, Address, MemoryError, Pod};
+
+/// Terminated strings are read page by page, so that a string ending right before an inaccessible
+/// page can still be read.
+const PAGE_SIZE: usize = 0x1000;
+
+/// Length of the buffer MSVC's `std::string` keeps short strings in, terminator included.
+const MSVC_INLINE_LEN: usize = 16;
+
+/// Address `offset` bytes into the struct at `address`.
+fn offset(address: Address, offset: usize) -> Result<Address, MemoryError> {
+    address.checked_add(offset).ok_or(MemoryError::Overflow {
+        address,
+        len: offset,
+    })
+}
+
+/// Checks that the string at `address` is no longer than `max_len`.
+fn check_len(address: Address, len: usize, max_len: usize) -> Result<(), MemoryError> {
+    if len > max_len {
+        return Err(MemoryError::StringTooLong { address, max_len });
+    }
+
+    Ok(())
+}
+
+/// Reads the code units starting at `address` up to the first `0`, which isn't included.
+fn read_terminated<T: Pod + Default + PartialEq>(
+    address: Address,
+    max_len: usize,
+) -> Result<Vec<T>, MemoryError> {
+    let mut units = Vec::new();
+    let mut next = address;
+    loop {
+        // Room is left for the terminator, which is only looked for up to `max_len`.
+        let remaining = max_len.saturating_add(1).saturating_sub(units.len());
+        if remaining == 0 {
+            return Err(MemoryError::StringTooLong { address, max_len });
+        }
+
+        let to_page_end = (PAGE_SIZE - next.get() % PAGE_SIZE) / size_of::<T>();
+        let chunk = read_slice::<T>(next, to_page_end.clamp(1, remaining))?;
+        if let Some(end) = chunk.iter().position(|unit| *unit == T::default()) {
+            units.extend_from_slice(&chunk[..end]);
+            return Ok(units);
+        }
+
+        next = offset(next, chunk.len() * size_of::<T>())?;
+        units.extend(chunk);
+    }
+}
+
+/// Decodes the UTF-8 string read at `address`.
+fn decode_utf8(address: Address, bytes: Vec<u8>) -> Result<String, MemoryError> {
+    String::from_utf8(bytes).map_err(|_| MemoryError::InvalidString(address))
+}
+
+/// Decodes the UTF-16 string read at `address`.
+fn decode_utf16(address: Address, units: &[u16]) -> Result<String, MemoryError> {
+    String::from_utf16(units).map_err(|_| MemoryError::InvalidString(address))
+}
+
+/// Reads the `len` bytes of UTF-8 at `address`.
+pub fn read_utf8(address: Address, len: usize) -> Result<String, MemoryError> {
+    if len == 0 {
+        return Ok(String::new());
+    }
+
+    decode_utf8(address, read_slice(address, len)?)
+}
+
+/// Reads the `len` code units of UTF-16 at `address`.
+pub fn read_utf16(address: Address, len: usize) -> Result<String, MemoryError> {
+    if len == 0 {
+        return Ok(String::new());
+    }
+
+    decode_utf16(address, &read_slice(address, len)?)
+}
+
+/// Reads the null-terminated UTF-8 string at `address`, of at most `max_len` bytes.
+pub fn read_c_string(address: Address, max_len: usize) -> Result<String, MemoryError> {
+    decode_utf8(address, read_terminated(address, max_len)?)
+}
+
+/// Reads the null-terminated UTF-16 string at `address`, such as a `wchar_t` string on Windows, of
+/// at most `max_len` code units.
+pub fn read_c_string_utf16(address: Address, max_len: usize) -> Result<String, MemoryError> {
+    decode_utf16(address, &read_terminated(address, max_len)?)
+}
+
+/// Reads the UTF-8 string at `address` which starts with its length in bytes as an `L`, such as a
+/// `u32`, of at most `max_len` bytes.
+pub fn read_prefixed_string<L: Pod + TryInto<usize>>(
+    address: Address,
+    max_len: usize,
+) -> Result<String, MemoryError> {
+    let len = read_unaligned::<L>(address)?
+        .try_into()
+        .map_err(|_| MemoryError::InvalidString(address))?;
+
+    check_len(address, len, max_len)?;
+    read_utf8(offset(address, size_of::<L>())?, len)
+}
+
+/// Reads the .NET `System.String` object at `address`, such as one of Unity's, of at most
+/// `max_len` code units.
+/// Both Mono and IL2CPP lay it out as the object header, two pointers, followed by the length as
+/// an `i32` and the UTF-16 code units.
+pub fn read_dotnet_string(address: Address, max_len: usize) -> Result<String, MemoryError> {
+    let length = offset(address, 2 * size_of::<usize>())?;
+    let len =
+        usize::try_from(read::<i32>(length)?).map_err(|_| MemoryError::InvalidString(address))?;
+
+    check_len(address, len, max_len)?;
+    read_utf16(offset(length, size_of::<i32>())?, len)
+}
+
+/// Reads the MSVC `std::string` at `address`, of at most `max_len` bytes.
+/// It starts with a 16 byte buffer, which holds strings of up to 15 bytes and a pointer to longer
+/// ones, followed by the length and the capacity.
+pub fn read_msvc_string(address: Address, max_len: usize) -> Result<String, MemoryError> {
+    let len = read::<usize>(offset(address, MSVC_INLINE_LEN)?)?;
+    let capacity = read::<usize>(offset(address, MSVC_INLINE_LEN + size_of::<usize>())?)?;
+    if len > capacity {
+        return Err(MemoryError::InvalidString(address));
+    }
+
+    check_len(address, len, max_len)?;
+    let data = if capacity < MSVC_INLINE_LEN {
+        address
+    } else {
+        read::<Address>(address)?
+    };
+
+    read_utf8(data, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address<T: ?Sized>(value: &T) -> Address {
+        Address::from_ptr(value)
+    }
+
+    /// MSVC's `std::string`, see `read_msvc_string`.
+    #[repr(C)]
+    struct MsvcString {
+        buffer: [u8; MSVC_INLINE_LEN],
+        len: usize,
+        capacity: usize,
+    }
+
+    impl MsvcString {
+        fn inline(text: &str) -> Self {
+            let mut buffer = [0; MSVC_INLINE_LEN];
+            buffer[..text.len()].copy_from_slice(text.as_bytes());
+            Self {
+                buffer,
+                len: text.len(),
+                capacity: MSVC_INLINE_LEN - 1,
+            }
+        }
+
+        fn heap(text: &[u8]) -> Self {
+            let mut buffer = [0; MSVC_INLINE_LEN];
+            buffer[..size_of::<usize>()].copy_from_slice(&(text.as_ptr() as usize).to_ne_bytes());
+            Self {
+                buffer,
+                len: text.len(),
+                capacity: text.len().max(MSVC_INLINE_LEN),
+            }
+        }
+    }
+
+    /// .NET's `System.String`, see `read_dotnet_string`.
+    #[repr(C)]
+    struct DotnetString {
+        header: [usize; 2],
+        len: i32,
+        chars: [u16; 8],
+    }
+
+    impl DotnetString {
+        fn new(text: &str) -> Self {
+            let mut chars = [0; 8];
+            let units = text.encode_utf16().collect::<Vec<_>>();
+            chars[..units.len()].copy_from_slice(&units);
+            Self {
+                header: [0; 2],
+                len: units.len() as i32,
+                chars,
+            }
+        }
+    }
+
+    #[test]
+    fn strings_of_known_length_are_read() {
+        let text = "héllo";
+        let units = text.encode_utf16().collect::<Vec<_>>();
+        assert_eq!(read_utf8(address(text), text.len()).as_deref(), Ok(text));
+        assert_eq!(
+            read_utf16(address(&*units), units.len()).as_deref(),
+            Ok(text)
+        );
+        assert_eq!(read_utf8(Address::NULL, 0).as_deref(), Ok(""));
+    }
+
+    #[test]
+    fn terminated_strings_are_read() {
+        let text = b"hello\0world";
+        assert_eq!(read_c_string(address(text), 5).as_deref(), Ok("hello"));
+        assert_eq!(
+            read_c_string(address(text), 4),
+            Err(MemoryError::StringTooLong {
+                address: address(text),
+                max_len: 4
+            })
+        );
+
+        let units = "wide\0".encode_utf16().collect::<Vec<_>>();
+        assert_eq!(
+            read_c_string_utf16(address(&*units), 16).as_deref(),
+            Ok("wide")
+        );
+        assert_eq!(read_c_string(address(b"\0"), 0).as_deref(), Ok(""));
+    }
+
+    #[test]
+    fn invalid_strings_are_errors() {
+        let bytes = b"\xFF\xFEok\0";
+        let invalid = Err(MemoryError::InvalidString(address(bytes)));
+        assert_eq!(read_c_string(address(bytes), 16), invalid);
+        assert_eq!(read_utf8(address(bytes), 2), invalid);
+
+        let units = [0xD800u16, 0];
+        assert_eq!(
+            read_c_string_utf16(address(&units), 16),
+            Err(MemoryError::InvalidString(address(&units)))
+        );
+    }
+
+    #[test]
+    fn prefixed_strings_are_read() {
+        let mut prefixed = 5u32.to_ne_bytes().to_vec();
+        prefixed.extend_from_slice(b"hello");
+        let at = address(&*prefixed);
+        assert_eq!(read_prefixed_string::<u32>(at, 5).as_deref(), Ok("hello"));
+        assert_eq!(
+            read_prefixed_string::<u32>(at, 4),
+            Err(MemoryError::StringTooLong {
+                address: at,
+                max_len: 4
+            })
+        );
+
+        let negative = (-1i32).to_ne_bytes();
+        assert_eq!(
+            read_prefixed_string::<i32>(address(&negative), 16),
+            Err(MemoryError::InvalidString(address(&negative)))
+        );
+    }
+
+    #[test]
+    fn dotnet_strings_are_read() {
+        let string = DotnetString::new("Unity");
+        assert_eq!(
+            read_dotnet_string(address(&string), 5).as_deref(),
+            Ok("Unity")
+        );
+        assert_eq!(
+            read_dotnet_string(address(&string), 4),
+            Err(MemoryError::StringTooLong {
+                address: address(&string),
+                max_len: 4
+            })
+        );
+
+        let corrupt = DotnetString { len: -1, ..string };
+        assert_eq!(
+            read_dotnet_string(address(&corrupt), 16),
+            Err(MemoryError::InvalidString(address(&corrupt)))
+        );
+    }
+
+    #[test]
+    fn msvc_strings_are_read() {
+        let inline = MsvcString::inline("fifteen bytes!!");
+        assert_eq!(
+            read_msvc_string(address(&inline), 15).as_deref(),
+            Ok("fifteen bytes!!")
+        );
+
+        let text = b"a string too long to be kept inline";
+        let heap = MsvcString::heap(text);
+        assert_eq!(
+            read_msvc_string(address(&heap), 64).as_deref(),
+            Ok("a string too long to be kept inline")
+        );
+        assert_eq!(
+            read_msvc_string(address(&heap), 16),
+            Err(MemoryError::StringTooLong {
+                address: address(&heap),
+                max_len: 16
+            })
+        );
+
+        let corrupt = MsvcString {
+            len: 16,
+            ..MsvcString::inline("short")
+        };
+        assert_eq!(
+            read_msvc_string(address(&corrupt), 64),
+            Err(MemoryError::InvalidString(address(&corrupt)))
+        );
+
+        let invalid = b"\xFF invalid UTF-8 on the heap";
+        assert_eq!(
+            read_msvc_string(address(&MsvcString::heap(invalid)), 64),
+            Err(MemoryError::InvalidString(address(invalid)))
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use super::{bytes_of, read_unaligned, regions, Address, MemoryError, Pod};
+use std::fmt::Display;
+
+/// Most candidates a first scan keeps, so that scanning for a common value such as `0` doesn't
+/// exhaust the game's memory.
+pub const MAX_CANDIDATES: usize = 1 << 20;
+
+/// Bytes copied out of the scanned memory at once.
+const CHUNK_SIZE: usize = 1 << 16;
+
+/// Value which can be searched for by a `ValueScan`.
+pub trait Scannable: Pod + PartialOrd + Display {}
+
+macro_rules! impl_scannable {
+    ($($ty:ty),*) => {
+        $(impl Scannable for $ty {})*
+    };
+}
+
+impl_scannable!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+/// Which candidates a refinement pass keeps, by comparing their current value to the one they had
+/// during the previous pass.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Refinement<T> {
+    /// The value is now equal to this one.
+    Equals(T),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl<T: Scannable> Refinement<T> {
+    /// Checks whether a candidate whose value went from `previous` to `current` is kept.
+    pub fn keeps(&self, previous: T, current: T) -> bool {
+        match self {
+            Self::Equals(value) => current == *value,
+            Self::Changed => current != previous,
+            Self::Unchanged => current == previous,
+            Self::Increased => current > previous,
+            Self::Decreased => current < previous,
+        }
+    }
+}
+
+/// Cheat Engine-style scan for the addresses of a value: a first scan finds every aligned
+/// occurrence of the value, and refinement passes narrow the candidates down as the value changes
+/// in the game.
+#[derive(Clone, Debug)]
+pub struct ValueScan<T> {
+    /// Candidate addresses, in ascending order, along with their value during the last pass.
+    candidates: Vec<(Address, T)>,
+
+    /// Whether the first scan stopped at `MAX_CANDIDATES`.
+    truncated: bool,
+}
+
+impl<T: Scannable> ValueScan<T> {
+    /// Scans every readable and writable region of the process for `value`, which is where the
+    /// game keeps its state.
+    /// The stack of the scanning thread is skipped, since it holds copies of `value` itself. Other
+    /// copies the payload made, such as in memory it has freed since, are found along with the
+    /// game's and fall away as the scan is refined.
+    pub fn first(value: T) -> Self {
+        let local = 0u8;
+        let own_stack = Address::from_ptr(&local);
+        let spans = regions()
+            .into_iter()
+            .filter(|region| {
+                region.protection.read && region.protection.write && !region.contains(own_stack)
+            })
+            .map(|region| (region.start, region.len()));
+
+        Self::scan(value, spans)
+    }
+
+    /// Scans the `len` bytes at `start` for `value`, which have to be readable.
+    pub fn first_in(value: T, start: Address, len: usize) -> Result<Self, MemoryError> {
+        super::check_readable(start, len, 1)?;
+        Ok(Self::scan(value, std::iter::once((start, len))))
+    }
+
+    /// Collects the aligned occurrences of `value` in readable spans of memory.
+    fn scan(value: T, spans: impl Iterator<Item = (Address, usize)>) -> Self {
+        // Values are compared byte by byte, so a float only matches if it's exactly equal.
+        let bytes = bytes_of(&value);
+        let (size, align) = (size_of::<T>(), align_of::<T>());
+
+        // Spans are copied out chunk by chunk rather than searched in place, and the chunk is
+        // skipped, since it holds copies of the value. Only addresses are collected while scanning,
+        // so that the candidates don't hold any either.
+        let (mut found, mut truncated) = (Vec::new(), false);
+        let mut chunk = vec![0u8; CHUNK_SIZE + size - 1];
+        let chunk_range = chunk.as_ptr_range();
+        'spans: for (start, len) in spans {
+            for offset in (0..len).step_by(CHUNK_SIZE) {
+                let from = Address::new(start.get() + offset);
+                let chunk_len = (len - offset).min(chunk.len());
+
+                // Chunks which have been unmapped since the span was listed are skipped.
+                if super::region::read_into(from, &mut chunk[..chunk_len]).is_err() {
+                    continue;
+                }
+
+                let first = from.get().next_multiple_of(align) - from.get();
+                let end = (chunk_len + 1).saturating_sub(size);
+                for index in (first..end).step_by(align) {
+                    let address = Address::new(from.get() + index);
+                    if chunk[index..index + size] != *bytes
+                        || chunk_range.contains(&address.as_ptr())
+                    {
+                        continue;
+                    }
+
+                    if found.len() == MAX_CANDIDATES {
+                        truncated = true;
+                        break 'spans;
+                    }
+
+                    found.push(address);
+                }
+            }
+        }
+
+        Self {
+            candidates: found.into_iter().map(|address| (address, value)).collect(),
+            truncated,
+        }
+    }
+
+    /// Reads the current value of every candidate, keeping those `refinement` keeps.
+    /// Candidates which can't be read anymore are dropped.
+    pub fn refine(&mut self, refinement: Refinement<T>) {
+        self.candidates.retain_mut(|(address, previous)| {
+            let Ok(current) = read_unaligned::<T>(*address) else {
+                return false;
+            };
+
+            let kept = refinement.keeps(*previous, current);
+            *previous = current;
+            kept
+        });
+    }
+
+    /// Candidate addresses in ascending order, along with their value during the last pass.
+    pub fn candidates(&self) -> &[(Address, T)] {
+        &self.candidates
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    /// Checks whether the first scan found more than `MAX_CANDIDATES` occurrences and stopped.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Indices of the candidates of `scan` within `values`.
+    fn indices<T: Scannable>(scan: &ValueScan<T>, values: &[T]) -> Vec<usize> {
+        let start = Address::from_ptr(values.as_ptr());
+        scan.candidates()
+            .iter()
+            .map(|(address, _)| address.offset_from(start).unwrap() / size_of::<T>())
+            .collect()
+    }
+
+    /// First scan for `100` in `values`.
+    fn first(values: &[i32]) -> ValueScan<i32> {
+        let start = Address::from_ptr(values.as_ptr());
+        ValueScan::first_in(100, start, size_of_val(values)).unwrap()
+    }
+
+    #[test]
+    fn first_scans_find_aligned_occurrences() {
+        let values = Box::new([100, 5, 100, 100, 0, -100, 100]);
+        let scan = first(&*values);
+        assert_eq!(indices(&scan, &*values), [0, 2, 3, 6]);
+        assert!(scan.candidates().iter().all(|(_, value)| *value == 100));
+        assert!(!scan.is_truncated());
+
+        // Occurrences straddling two aligned values aren't candidates.
+        let mut bytes = Box::new([0u8; 12]);
+        bytes[1..5].copy_from_slice(&100i32.to_ne_bytes());
+        bytes[8..].copy_from_slice(&100i32.to_ne_bytes());
+        let start = Address::from_ptr(bytes.as_ptr());
+        let scan = ValueScan::first_in(100i32, start, bytes.len()).unwrap();
+        assert_eq!(scan.candidates(), [(start.checked_add(8).unwrap(), 100)]);
+    }
+
+    #[test]
+    fn first_scans_skip_their_own_memory() {
+        // A value which only exists at runtime, so that it isn't part of the binary.
+        let value = u64::from(std::process::id()) | 0x5A5A_0000_0000_0000;
+        assert!(ValueScan::first(value).is_empty());
+
+        let stored = Box::new(value);
+        let scan = ValueScan::first(value);
+        assert_eq!(scan.candidates(), [(Address::from_ptr(&*stored), value)]);
+    }
+
+    #[test]
+    fn first_scans_stop_at_the_maximum() {
+        let values = vec![0u8; MAX_CANDIDATES + 1];
+        let start = Address::from_ptr(values.as_ptr());
+        let scan = ValueScan::first_in(0u8, start, values.len()).unwrap();
+        assert_eq!(scan.len(), MAX_CANDIDATES);
+        assert!(scan.is_truncated());
+
+        let scan = ValueScan::first_in(0u8, start, MAX_CANDIDATES).unwrap();
+        assert!(!scan.is_truncated());
+    }
+
+    #[test]
+    fn refinements_narrow_candidates_down() {
+        let mut values = Box::new([100; 5]);
+        let mut scan = first(&*values);
+        values[1] = 90;
+        values[2] = 110;
+        values[4] = 85;
+
+        let refined = |refinement| {
+            let mut scan = scan.clone();
+            scan.refine(refinement);
+            indices(&scan, &*values)
+        };
+
+        assert_eq!(refined(Refinement::Changed), [1, 2, 4]);
+        assert_eq!(refined(Refinement::Unchanged), [0, 3]);
+        assert_eq!(refined(Refinement::Increased), [2]);
+        assert_eq!(refined(Refinement::Decreased), [1, 4]);
+        assert_eq!(refined(Refinement::Equals(85)), [4]);
+
+        // Refinements compare against the values of the previous pass.
+        scan.refine(Refinement::Decreased);
+        values[1] = 95;
+        scan.refine(Refinement::Increased);
+        assert_eq!(indices(&scan, &*values), [1]);
+        assert_eq!(scan.candidates()[0].1, 95);
+    }
+}
@@ -0,0 +1,685 @@
+use crate::{abi::*, functions::*};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// A single call made by the payload into the host.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Call {
+    Log(String),
+    MemoryReadString(i64),
+    PXScriptExecute {
+        code: String,
+        send_to_party: bool,
+        use_main_thread: bool,
+    },
+    GetDeltaTime,
+    EjectPayload {
+        process: ProcessHandle,
+        payload: ModuleHandle,
+    },
+    RuneVMExecute(String),
+    CreateThreadKey(String),
+    SetThreadKeyValue(String, bool),
+    GetThreadKey(String),
+    AddWindow(String),
+    FocusWindow(String),
+    AddLabel {
+        identifier: String,
+        text: String,
+        font_id: usize,
+    },
+    AddButton {
+        identifier: String,
+        text: String,
+        source: String,
+        has_callback: bool,
+    },
+    AddSeparator(String),
+    AddSpacing {
+        identifier: String,
+        x: f32,
+        y: f32,
+    },
+    GetI32SliderValue(String),
+    GetF32SliderValue(String),
+    SetNextItemSameLine(String),
+    AddCustomFontLabel {
+        identifier: String,
+        text: String,
+        relative_font_path: String,
+    },
+    RemoveWidget(String),
+    RemoveAllWidgets,
+    AddI32Slider {
+        identifier: String,
+        text: String,
+        min: i32,
+        max: i32,
+        rune_code: Option<String>,
+    },
+    AddF32Slider {
+        identifier: String,
+        text: String,
+        min: f32,
+        max: f32,
+        rune_code: Option<String>,
+    },
+    SellixIsPayingForProduct {
+        product_id: String,
+        bearer_token: String,
+    },
+    ConfigHasSerial(String),
+}
+
+/// Everything the mock host remembers, shared by all of the `extern "C"` functions below.
+#[derive(Default)]
+struct MockState {
+    calls: Vec<Call>,
+    ejected: bool,
+    delta_time: f32,
+    memory_strings: HashMap<i64, &'static str>,
+    thread_keys: HashMap<String, bool>,
+    i32_sliders: HashMap<String, i32>,
+    f32_sliders: HashMap<String, f32>,
+    button_callbacks: HashMap<String, extern "C" fn()>,
+    paid_products: HashMap<String, bool>,
+    serials: Vec<String>,
+}
+
+/// State of the mock host.
+static STATE: Mutex<Option<MockState>> = Mutex::new(None);
+
+/// Serializes tests, since there's only one global function table per process.
+static EXCLUSIVE: Mutex<()> = Mutex::new(());
+
+/// Fake process handle passed to the payload by `MockHost::run_gateway`.
+pub const MOCK_PROCESS: ProcessHandle = ProcessHandle(std::ptr::without_provenance_mut(0x1));
+
+/// Fake module handle passed to the payload by `MockHost::run_gateway`.
+pub const MOCK_PAYLOAD: ModuleHandle = ModuleHandle(std::ptr::without_provenance_mut(0x2));
+
+/// Runs `f` against the mock state.
+fn with_state<T>(f: impl FnOnce(&mut MockState) -> T) -> T {
+    let mut state = STATE.lock().unwrap_or_else(PoisonError::into_inner);
+    f(state.get_or_insert_with(Default::default))
+}
+
+/// Records `call`.
+fn record(call: Call) {
+    with_state(|state| state.calls.push(call));
+}
+
+/// Copies a string passed by the payload.
+fn owned(string: FfiStr) -> String {
+    // SAFETY: The payload keeps the string alive for the duration of the call.
+    unsafe { string.as_str() }.unwrap_or("<invalid>").to_owned()
+}
+
+/// Copies an optional string passed by the payload.
+fn owned_option(string: FfiStr) -> Option<String> {
+    (!string.is_null()).then(|| owned(string))
+}
+
+extern "C" fn dynamic_log(message: FfiStr) {
+    record(Call::Log(owned(message)));
+}
+
+extern "C" fn memory_read_string(address: i64) -> FfiStr {
+    with_state(|state| {
+        state.calls.push(Call::MemoryReadString(address));
+        state
+            .memory_strings
+            .get(&address)
+            .map_or_else(FfiStr::null, |string| FfiStr::new(string))
+    })
+}
+
+extern "C" fn pxscript_execute(code: FfiStr, send_to_party: bool, use_main_thread: bool) {
+    record(Call::PXScriptExecute {
+        code: owned(code),
+        send_to_party,
+        use_main_thread,
+    });
+}
+
+extern "C" fn dynamic_get_delta_time() -> f32 {
+    with_state(|state| {
+        state.calls.push(Call::GetDeltaTime);
+        state.delta_time
+    })
+}
+
+extern "C" fn dynamic_eject_payload(process: ProcessHandle, payload: ModuleHandle) {
+    with_state(|state| {
+        state.ejected = true;
+        state.calls.push(Call::EjectPayload { process, payload });
+    });
+}
+
+extern "C" fn rune_vm_execute(source: FfiStr) {
+    record(Call::RuneVMExecute(owned(source)));
+}
+
+extern "C" fn dynamic_add_thread_key(identifier: FfiStr) {
+    let identifier = owned(identifier);
+    with_state(|state| {
+        state.thread_keys.entry(identifier.clone()).or_default();
+        state.calls.push(Call::CreateThreadKey(identifier));
+    });
+}
+
+extern "C" fn dynamic_set_thread_key_value(identifier: FfiStr, value: bool) {
+    let identifier = owned(identifier);
+    with_state(|state| {
+        state.thread_keys.insert(identifier.clone(), value);
+        state.calls.push(Call::SetThreadKeyValue(identifier, value));
+    });
+}
+
+extern "C" fn dynamic_get_thread_key(identifier: FfiStr) -> bool {
+    let identifier = owned(identifier);
+    with_state(|state| {
+        let value = state.thread_keys.get(&identifier).copied().unwrap_or(false);
+        state.calls.push(Call::GetThreadKey(identifier));
+        value
+    })
+}
+
+extern "C" fn ui_add_window(name: FfiStr) {
+    record(Call::AddWindow(owned(name)));
+}
+
+extern "C" fn ui_focus_window(name: FfiStr) {
+    record(Call::FocusWindow(owned(name)));
+}
+
+extern "C" fn ui_add_label(identifier: FfiStr, text: FfiStr, font_id: usize) {
+    record(Call::AddLabel {
+        identifier: owned(identifier),
+        text: owned(text),
+        font_id,
+    });
+}
+
+extern "C" fn ui_add_button(
+    identifier: FfiStr,
+    text: FfiStr,
+    source: FfiStr,
+    callback: Option<extern "C" fn()>,
+) {
+    let identifier = owned(identifier);
+    with_state(|state| {
+        if let Some(callback) = callback {
+            state.button_callbacks.insert(identifier.clone(), callback);
+        }
+
+        state.calls.push(Call::AddButton {
+            identifier,
+            text: owned(text),
+            source: owned(source),
+            has_callback: callback.is_some(),
+        });
+    });
+}
+
+extern "C" fn ui_add_separator(identifier: FfiStr) {
+    record(Call::AddSeparator(owned(identifier)));
+}
+
+extern "C" fn ui_add_spacing(identifier: FfiStr, x: f32, y: f32) {
+    record(Call::AddSpacing {
+        identifier: owned(identifier),
+        x,
+        y,
+    });
+}
+
+extern "C" fn ui_get_i32_slider_value(identifier: FfiStr) -> i32 {
+    let identifier = owned(identifier);
+    with_state(|state| {
+        let value = state.i32_sliders.get(&identifier).copied().unwrap_or(0);
+        state.calls.push(Call::GetI32SliderValue(identifier));
+        value
+    })
+}
+
+extern "C" fn ui_get_f32_slider_value(identifier: FfiStr) -> f32 {
+    let identifier = owned(identifier);
+    with_state(|state| {
+        let value = state.f32_sliders.get(&identifier).copied().unwrap_or(0.0);
+        state.calls.push(Call::GetF32SliderValue(identifier));
+        value
+    })
+}
+
+extern "C" fn ui_set_next_item_same_line(identifier: FfiStr) {
+    record(Call::SetNextItemSameLine(owned(identifier)));
+}
+
+extern "C" fn ui_add_custom_font_label(
+    identifier: FfiStr,
+    text: FfiStr,
+    relative_font_path: FfiStr,
+) {
+    record(Call::AddCustomFontLabel {
+        identifier: owned(identifier),
+        text: owned(text),
+        relative_font_path: owned(relative_font_path),
+    });
+}
+
+extern "C" fn ui_remove_widget(identifier: FfiStr) {
+    record(Call::RemoveWidget(owned(identifier)));
+}
+
+extern "C" fn ui_remove_all_widgets() {
+    record(Call::RemoveAllWidgets);
+}
+
+extern "C" fn ui_add_i32_slider(
+    identifier: FfiStr,
+    text: FfiStr,
+    min: i32,
+    max: i32,
+    rune_code: FfiStr,
+) {
+    record(Call::AddI32Slider {
+        identifier: owned(identifier),
+        text: owned(text),
+        min,
+        max,
+        rune_code: owned_option(rune_code),
+    });
+}
+
+extern "C" fn ui_add_f32_slider(
+    identifier: FfiStr,
+    text: FfiStr,
+    min: f32,
+    max: f32,
+    rune_code: FfiStr,
+) {
+    record(Call::AddF32Slider {
+        identifier: owned(identifier),
+        text: owned(text),
+        min,
+        max,
+        rune_code: owned_option(rune_code),
+    });
+}
+
+extern "C" fn sellix_is_paying_for_product(product_id: FfiStr, bearer_token: FfiStr) -> bool {
+    let product_id = owned(product_id);
+    with_state(|state| {
+        let paying = state
+            .paid_products
+            .get(&product_id)
+            .copied()
+            .unwrap_or(false);
+        state.calls.push(Call::SellixIsPayingForProduct {
+            product_id,
+            bearer_token: owned(bearer_token),
+        });
+        paying
+    })
+}
+
+extern "C" fn config_has_serial(serial: FfiStr) -> bool {
+    let serial = owned(serial);
+    with_state(|state| {
+        let present = state.serials.contains(&serial);
+        state.calls.push(Call::ConfigHasSerial(serial));
+        present
+    })
+}
+
+/// Fake dynamic host for running payloads under `cargo test` on any platform.
+/// The mock records every call the payload makes, and hands out values which tests script up
+/// front through the `set_*` functions.
+/// Only one instance can be alive at a time, creating a second one blocks until the first one has
+/// been dropped, so that tests running in parallel don't see each other's calls.
+pub struct MockHost {
+    table: Box<DNXFunctions>,
+    _exclusive: MutexGuard<'static, ()>,
+}
+
+impl MockHost {
+    /// Creates a mock host with a complete function table and no recorded calls.
+    pub fn new() -> Self {
+        let exclusive = EXCLUSIVE.lock().unwrap_or_else(PoisonError::into_inner);
+        *STATE.lock().unwrap_or_else(PoisonError::into_inner) = Some(MockState::default());
+        crate::logging::reset_throttle();
+
+        // Functions added to the table later on are left unsupported until the mock learns them.
+        let mut table = Box::new(DNXFunctions::unsupported(dynamic_log));
+        table.memory_read_string = Some(memory_read_string);
+        table.pxscript_execute = Some(pxscript_execute);
+        table.dynamic_get_delta_time = Some(dynamic_get_delta_time);
+        table.dynamic_eject_payload = Some(dynamic_eject_payload);
+        table.rune_vm_execute = Some(rune_vm_execute);
+        table.dynamic_add_thread_key = Some(dynamic_add_thread_key);
+        table.dynamic_set_thread_key_value = Some(dynamic_set_thread_key_value);
+        table.dynamic_get_thread_key = Some(dynamic_get_thread_key);
+        table.ui_add_window = Some(ui_add_window);
+        table.ui_focus_window = Some(ui_focus_window);
+        table.ui_add_label = Some(ui_add_label);
+        table.ui_add_button = Some(ui_add_button);
+        table.ui_add_separator = Some(ui_add_separator);
+        table.ui_add_spacing = Some(ui_add_spacing);
+        table.ui_get_i32_slider_value = Some(ui_get_i32_slider_value);
+        table.ui_get_f32_slider_value = Some(ui_get_f32_slider_value);
+        table.ui_set_next_item_same_line = Some(ui_set_next_item_same_line);
+        table.ui_add_custom_font_label = Some(ui_add_custom_font_label);
+        table.ui_remove_widget = Some(ui_remove_widget);
+        table.ui_remove_all_widgets = Some(ui_remove_all_widgets);
+        table.ui_add_i32_slider = Some(ui_add_i32_slider);
+        table.ui_add_f32_slider = Some(ui_add_f32_slider);
+        table.sellix_is_paying_for_product = Some(sellix_is_paying_for_product);
+        table.config_has_serial = Some(config_has_serial);
+
+        Self {
+            table,
+            _exclusive: exclusive,
+        }
+    }
+
+    /// Overrides the header of the function table, for simulating older or incompatible hosts.
+    /// Shrinking `size` makes the payload treat every function past it as unsupported.
+    pub fn set_header(&mut self, header: DNXHeader) {
+        self.table.header = header;
+    }
+
+    /// Raw function table, as the real host would pass it to `arctic_gateway`.
+    pub fn table(&self) -> *const DNXFunctions {
+        &*self.table
+    }
+
+    /// Validates the table and stores it in `FUNCTIONS`, without going through `arctic_gateway`.
+    /// The table replaces whatever an earlier mock installed, and is removed again once the mock
+    /// is dropped.
+    pub fn install(&self) -> Result<&'static DNXFunctions, AbiMismatch> {
+        // SAFETY: The table is owned by `self` and `from_raw` copies it.
+        let functions = unsafe { DNXFunctions::from_raw(self.table()) }?;
+        FUNCTIONS.replace_for_test(functions);
+        Ok(functions)
+    }
+
+    /// Calls the payload's `arctic_gateway` with `MOCK_PROCESS`, `MOCK_PAYLOAD` and this host's
+    /// function table.
+    pub fn run_gateway(&self) {
+        // SAFETY: The table is owned by `self`, and copied by the payload.
+        unsafe { crate::arctic_gateway(MOCK_PROCESS, MOCK_PAYLOAD, self.table()) }
+    }
+
+    /// Every call recorded so far.
+    pub fn calls(&self) -> Vec<Call> {
+        with_state(|state| state.calls.clone())
+    }
+
+    /// Every call recorded so far, clearing the record.
+    pub fn take_calls(&self) -> Vec<Call> {
+        with_state(|state| std::mem::take(&mut state.calls))
+    }
+
+    /// Every message logged through `dynamic_log` so far.
+    pub fn logs(&self) -> Vec<String> {
+        with_state(|state| {
+            state
+                .calls
+                .iter()
+                .filter_map(|call| match call {
+                    Call::Log(message) => Some(message.clone()),
+                    _ => None,
+                })
+                .collect()
+        })
+    }
+
+    /// Checks whether the payload has asked to be ejected.
+    /// Unlike the recorded calls, this isn't reset by `take_calls`.
+    pub fn ejected(&self) -> bool {
+        with_state(|state| state.ejected)
+    }
+
+    /// Current value of a thread-key, `None` if it hasn't been created or set.
+    pub fn thread_key(&self, identifier: &str) -> Option<bool> {
+        with_state(|state| state.thread_keys.get(identifier).copied())
+    }
+
+    /// Sets the value of a thread-key, as if another payload or the host changed it.
+    pub fn set_thread_key(&self, identifier: &str, value: bool) {
+        with_state(|state| state.thread_keys.insert(identifier.to_owned(), value));
+    }
+
+    /// Sets the value returned by `dynamic_get_delta_time`.
+    pub fn set_delta_time(&self, delta_time: f32) {
+        with_state(|state| state.delta_time = delta_time);
+    }
+
+    /// Sets the string returned by `memory_read_string` for `address`.
+    /// Unknown addresses return a null string.
+    pub fn set_memory_string(&self, address: i64, string: &str) {
+        let string = Box::leak(string.to_owned().into_boxed_str());
+        with_state(|state| state.memory_strings.insert(address, string));
+    }
+
+    /// Sets the value returned by `ui_get_i32_slider_value` for `identifier`.
+    pub fn set_i32_slider_value(&self, identifier: &str, value: i32) {
+        with_state(|state| state.i32_sliders.insert(identifier.to_owned(), value));
+    }
+
+    /// Sets the value returned by `ui_get_f32_slider_value` for `identifier`.
+    pub fn set_f32_slider_value(&self, identifier: &str, value: f32) {
+        with_state(|state| state.f32_sliders.insert(identifier.to_owned(), value));
+    }
+
+    /// Sets whether `sellix_is_paying_for_product` reports the user as paying for `product_id`.
+    pub fn set_paying_for_product(&self, product_id: &str, paying: bool) {
+        with_state(|state| state.paid_products.insert(product_id.to_owned(), paying));
+    }
+
+    /// Adds a serial which `config_has_serial` reports as present.
+    pub fn add_serial(&self, serial: &str) {
+        with_state(|state| state.serials.push(serial.to_owned()));
+    }
+
+    /// Clicks the button called `identifier`, invoking its native callback if it has one.
+    /// Returns `false` if there's no such button with a callback.
+    pub fn click_button(&self, identifier: &str) -> bool {
+        // Don't hold the lock while the callback runs, it'll most likely call back into the host.
+        let callback = with_state(|state| state.button_callbacks.get(identifier).copied());
+        if let Some(callback) = callback {
+            callback();
+        }
+
+        callback.is_some()
+    }
+}
+
+impl Default for MockHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Removes the table installed by this mock, so that the next test starts without one.
+impl Drop for MockHost {
+    fn drop(&mut self) {
+        FUNCTIONS.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api, capabilities::Capability, error::GatewayError};
+
+    /// Header of a host which only knows the first `functions` functions after `dynamic_log`.
+    fn older_host(functions: usize) -> DNXHeader {
+        DNXHeader {
+            version: ABI_VERSION,
+            size: (std::mem::size_of::<DNXPrefix>() + functions * std::mem::size_of::<usize>())
+                as u32,
+        }
+    }
+
+    #[test]
+    fn calls_are_recorded() {
+        let host = MockHost::new();
+        host.install().unwrap();
+        host.set_delta_time(0.5);
+        host.set_i32_slider_value("speed", 7);
+
+        ui_add_window!("Arctic");
+        assert_eq!(get_delta_time!(), 0.5);
+        assert_eq!(get_i32_slider_value!("speed"), 7);
+        assert_eq!(
+            host.take_calls(),
+            [
+                Call::AddWindow("Arctic".to_owned()),
+                Call::GetDeltaTime,
+                Call::GetI32SliderValue("speed".to_owned()),
+            ]
+        );
+        assert!(host.calls().is_empty());
+    }
+
+    #[test]
+    fn macros_skip_invalid_arguments() {
+        let host = MockHost::new();
+        host.install().unwrap();
+
+        ui_add_window!("");
+        ui_add_label!("label", "a\0b", 0);
+        assert!(host.calls().is_empty());
+        ui_add_label!("label", "", 0);
+        assert_eq!(host.calls().len(), 1);
+    }
+
+    #[test]
+    fn thread_keys_are_shared_with_the_host() {
+        let host = MockHost::new();
+        host.install().unwrap();
+
+        create_thread_key!("eject");
+        assert_eq!(host.thread_key("eject"), Some(false));
+        host.set_thread_key("eject", true);
+        assert!(get_thread_key!("eject"));
+        assert_eq!(host.thread_key("missing"), None);
+    }
+
+    #[test]
+    fn slider_macros_take_owned_and_borrowed_code() {
+        let host = MockHost::new();
+        host.install().unwrap();
+
+        let code = String::from("print(value)");
+        ui_add_i32_slider!("owned", "Owned", 0, 10, Some(code.clone()));
+        ui_add_f32_slider!("borrowed", "Borrowed", 0.0, 1.0, Some(code.as_str()));
+        ui_add_i32_slider!("none", "None", 0, 10, None::<String>);
+        assert_eq!(
+            host.calls(),
+            [
+                Call::AddI32Slider {
+                    identifier: "owned".into(),
+                    text: "Owned".into(),
+                    min: 0,
+                    max: 10,
+                    rune_code: Some(code.clone()),
+                },
+                Call::AddF32Slider {
+                    identifier: "borrowed".into(),
+                    text: "Borrowed".into(),
+                    min: 0.0,
+                    max: 1.0,
+                    rune_code: Some(code),
+                },
+                Call::AddI32Slider {
+                    identifier: "none".into(),
+                    text: "None".into(),
+                    min: 0,
+                    max: 10,
+                    rune_code: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn buttons_invoke_their_callbacks() {
+        extern "C" fn clicked() {
+            log!("clicked");
+        }
+
+        let host = MockHost::new();
+        host.install().unwrap();
+        api::ui_add_button("button", "Click", "", Some(clicked)).unwrap();
+        assert!(host.click_button("button"));
+        assert!(!host.click_button("missing"));
+        assert_eq!(host.logs(), ["clicked"]);
+    }
+
+    #[test]
+    fn every_mock_installs_its_own_table() {
+        let host = MockHost::new();
+        host.install().unwrap();
+        assert!(api::pxscript_execute("print(1)", false, false).is_ok());
+        drop(host);
+
+        let mut host = MockHost::new();
+        assert_eq!(api::functions().err(), Some(GatewayError::NotInitialized));
+
+        host.set_header(older_host(1));
+        let functions = host.install().unwrap();
+        assert!(functions.supports(Capability::MemoryReadString));
+        assert_eq!(
+            api::pxscript_execute("print(1)", false, false),
+            Err(GatewayError::Unsupported(Capability::PXScriptExecute))
+        );
+        assert!(host.calls().is_empty());
+    }
+
+    #[test]
+    fn incompatible_tables_are_refused() {
+        let mut host = MockHost::new();
+        host.set_header(DNXHeader {
+            version: ABI_VERSION + 1,
+            size: std::mem::size_of::<DNXFunctions>() as u32,
+        });
+        assert!(host.install().is_err());
+        assert_eq!(api::functions().err(), Some(GatewayError::NotInitialized));
+    }
+
+    #[cfg(not(feature = "hot-reload"))]
+    #[test]
+    fn gateway_runs_repeatedly() {
+        for _ in 0..2 {
+            let host = MockHost::new();
+            host.run_gateway();
+            assert!(host
+                .logs()
+                .iter()
+                .any(|line| line.contains("Arctic Gateway template active!")));
+            assert!(host.ejected());
+            assert!(host.calls().contains(&Call::EjectPayload {
+                process: MOCK_PROCESS,
+                payload: MOCK_PAYLOAD,
+            }));
+        }
+    }
+
+    #[cfg(not(feature = "hot-reload"))]
+    #[test]
+    fn gateway_refuses_incompatible_hosts() {
+        let mut host = MockHost::new();
+        host.set_header(DNXHeader {
+            version: ABI_VERSION + 1,
+            size: std::mem::size_of::<DNXFunctions>() as u32,
+        });
+        host.run_gateway();
+        assert!(!host.ejected());
+        assert!(host.logs()[0].contains("refused to start"));
+    }
+}
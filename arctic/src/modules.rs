@@ -0,0 +1,600 @@
+use crate::{abi::ButtonCallback, api, gateway::Gateway, panic::catch_panic, payload::Payload};
+
+/// A hook owned by a module, enabled and disabled along with it.
+/// Implemented for retour's `StaticDetour`, as declared through `static_detour!`.
+pub trait Hook: Sync {
+    /// Enables the hook, does nothing if it already is.
+    ///
+    /// # Safety
+    /// Same as `StaticDetour::enable`.
+    unsafe fn enable(&self) -> Result<(), String>;
+
+    /// Disables the hook, does nothing if it already is.
+    ///
+    /// # Safety
+    /// Same as `StaticDetour::disable`.
+    unsafe fn disable(&self) -> Result<(), String>;
+
+    /// Checks whether the hook is enabled.
+    fn is_enabled(&self) -> bool;
+}
+
+impl<T: retour::Function> Hook for retour::StaticDetour<T> {
+    unsafe fn enable(&self) -> Result<(), String> {
+        retour::StaticDetour::enable(self).map_err(|error| error.to_string())
+    }
+
+    unsafe fn disable(&self) -> Result<(), String> {
+        retour::StaticDetour::disable(self).map_err(|error| error.to_string())
+    }
+
+    fn is_enabled(&self) -> bool {
+        retour::StaticDetour::is_enabled(self)
+    }
+}
+
+/// A single feature of the payload, see `ModuleRegistry`.
+/// Every callback runs with panics contained, a panic unloads the module without affecting the
+/// others.
+pub trait Module: Send + 'static {
+    /// Unique name of the module.
+    /// Prefixes the identifiers of its widgets, and names the `<name>.enabled` thread-key which
+    /// enables and disables it at runtime.
+    fn name(&self) -> &'static str;
+
+    /// Whether the module is enabled once it's loaded.
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    /// Thread-keys the module uses, created and reset to `false` before `on_load`.
+    fn thread_keys(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// Hooks owned by the module along with their names, enabled and disabled along with it.
+    /// They have to be initialized in `on_load`, for example through `enable_hook!`.
+    fn hooks(&self) -> Vec<(&'static str, &'static dyn Hook)> {
+        Vec::new()
+    }
+
+    /// Called once when the registry is loaded, before the module is enabled for the first time.
+    fn on_load(&mut self, _gateway: &Gateway) {}
+
+    /// Adds the module's widgets to its section of the registry's window, every time the module
+    /// is enabled.
+    fn build_ui(&mut self, _section: &mut Section) {}
+
+    /// Called every time the module is enabled, after its hooks and widgets have been added.
+    fn on_enable(&mut self, _gateway: &Gateway) {}
+
+    /// Called once per tick while the module is enabled.
+    fn on_tick(&mut self, _gateway: &Gateway, _delta_time: f32) {}
+
+    /// Called every time the module is disabled, before its hooks and widgets are removed.
+    fn on_disable(&mut self, _gateway: &Gateway) {}
+
+    /// Called last when the module is unloaded, for releasing anything it owns.
+    fn on_unload(&mut self) {}
+}
+
+/// The widgets of a single module inside the registry's window.
+/// Identifiers are prefixed with the module's name so that modules can't clash, and every widget
+/// is remembered so that it can be removed once the module is disabled.
+/// Every section starts with a `<name>#title` label showing the module's name and ends with a
+/// `<name>#end` separator, which can't clash with the module's own widgets, every method returns
+/// the full identifier of the widget it added.
+pub struct Section {
+    module: &'static str,
+    widgets: Vec<String>,
+}
+
+impl Section {
+    /// Full identifier of the widget called `identifier` in the section of `module`, for reading
+    /// slider values.
+    pub fn identifier(module: &str, identifier: &str) -> String {
+        format!("{module}.{identifier}")
+    }
+
+    /// Remembers the widget called `identifier`, returning its full identifier.
+    fn add(&mut self, identifier: &str) -> String {
+        let identifier = Self::identifier(self.module, identifier);
+        self.widgets.push(identifier.clone());
+        identifier
+    }
+
+    /// Adds the label which starts the section.
+    fn title(&mut self) {
+        let identifier = format!("{}#title", self.module);
+        ui_add_label!(identifier, self.module, 0);
+        self.widgets.push(identifier);
+    }
+
+    /// Adds the separator which ends the section.
+    fn end(&mut self) {
+        let identifier = format!("{}#end", self.module);
+        ui_add_separator!(identifier);
+        self.widgets.push(identifier);
+    }
+
+    /// Adds a label, see `ui_add_label!`.
+    pub fn label(&mut self, identifier: &str, text: &str, font_id: usize) -> String {
+        let identifier = self.add(identifier);
+        ui_add_label!(identifier, text, font_id);
+        identifier
+    }
+
+    /// Adds a label with a custom-loaded font, see `ui_add_custom_font_label!`.
+    pub fn custom_font_label(
+        &mut self,
+        identifier: &str,
+        text: &str,
+        relative_font_path: &str,
+    ) -> String {
+        let identifier = self.add(identifier);
+        ui_add_custom_font_label!(identifier, text, relative_font_path);
+        identifier
+    }
+
+    /// Adds a button, see `ui_add_button!`.
+    pub fn button(
+        &mut self,
+        identifier: &str,
+        text: &str,
+        source: &str,
+        callback: ButtonCallback,
+    ) -> String {
+        let identifier = self.add(identifier);
+        ui_add_button!(identifier, text, source, callback);
+        identifier
+    }
+
+    /// Adds a horizontal separator, see `ui_add_separator!`.
+    pub fn separator(&mut self, identifier: &str) -> String {
+        let identifier = self.add(identifier);
+        ui_add_separator!(identifier);
+        identifier
+    }
+
+    /// Adds spacing between widgets, see `ui_add_spacing!`.
+    pub fn spacing(&mut self, identifier: &str, x: f32, y: f32) -> String {
+        let identifier = self.add(identifier);
+        ui_add_spacing!(identifier, x, y);
+        identifier
+    }
+
+    /// Puts the next widget on the current line, see `ui_set_next_item_same_line!`.
+    pub fn same_line(&mut self, identifier: &str) -> String {
+        let identifier = self.add(identifier);
+        ui_set_next_item_same_line!(identifier);
+        identifier
+    }
+
+    /// Adds an i32 slider, see `ui_add_i32_slider!`.
+    pub fn i32_slider(
+        &mut self,
+        identifier: &str,
+        text: &str,
+        min: i32,
+        max: i32,
+        rune_code: Option<&str>,
+    ) -> String {
+        let identifier = self.add(identifier);
+        ui_add_i32_slider!(identifier, text, min, max, rune_code);
+        identifier
+    }
+
+    /// Adds an f32 slider, see `ui_add_f32_slider!`.
+    pub fn f32_slider(
+        &mut self,
+        identifier: &str,
+        text: &str,
+        min: f32,
+        max: f32,
+        rune_code: Option<&str>,
+    ) -> String {
+        let identifier = self.add(identifier);
+        ui_add_f32_slider!(identifier, text, min, max, rune_code);
+        identifier
+    }
+}
+
+/// A loaded module along with what the registry added on its behalf.
+struct Entry {
+    module: Box<dyn Module>,
+
+    /// Thread-key which enables and disables the module at runtime.
+    enable_thread_key: String,
+
+    /// Widgets of the module's section, empty while it's disabled.
+    widgets: Vec<String>,
+
+    /// Whether the module is currently enabled.
+    enabled: bool,
+}
+
+impl Entry {
+    /// Runs a module callback with panics contained, returning `false` if it panicked.
+    fn call(&mut self, callback_name: &str, callback: impl FnOnce(&mut dyn Module)) -> bool {
+        // Gives every event logged by the module its name as context.
+        let _span = tracing::info_span!("module", name = self.module.name()).entered();
+        let module = &mut *self.module;
+        let Err(message) = catch_panic(|| callback(module)) else {
+            return true;
+        };
+
+        tracing::error!(
+            "Module {} panicked in {callback_name}, unloading it! Message: {message}",
+            self.module.name()
+        );
+        false
+    }
+
+    /// Enables the module's hooks, builds its section and calls `on_enable`.
+    /// Returns `false` if the module panicked.
+    fn enable(&mut self, gateway: &Gateway, window: &str) -> bool {
+        if self.enabled {
+            return true;
+        }
+
+        self.enabled = true;
+        for (name, hook) in self.module.hooks() {
+            // SAFETY: Toggling hooks is only ever done from the tick thread.
+            if let Err(error) = unsafe { hook.enable() } {
+                tracing::error!("Failed enabling hook {name}, error: {error}");
+            }
+        }
+
+        ui_focus_window!(window);
+        let mut section = Section {
+            module: self.module.name(),
+            widgets: Vec::new(),
+        };
+
+        section.title();
+        let built = self.call("build_ui", |module| module.build_ui(&mut section));
+        section.end();
+        self.widgets = section.widgets;
+        built && self.call("on_enable", |module| module.on_enable(gateway))
+    }
+
+    /// Calls `on_disable`, and removes the module's section and hooks.
+    /// Returns `false` if the module panicked.
+    fn disable(&mut self, gateway: &Gateway, window: &str) -> bool {
+        if !self.enabled {
+            return true;
+        }
+
+        self.enabled = false;
+        let disabled = self.call("on_disable", |module| module.on_disable(gateway));
+        ui_focus_window!(window);
+        for widget in self.widgets.drain(..) {
+            ui_remove_widget!(widget);
+        }
+
+        self.disable_hooks();
+        disabled
+    }
+
+    /// Disables the module's hooks.
+    fn disable_hooks(&self) {
+        for (name, hook) in self.module.hooks() {
+            // SAFETY: Toggling hooks is only ever done from the tick thread.
+            if let Err(error) = unsafe { hook.disable() } {
+                tracing::error!("Failed disabling hook {name}, error: {error}");
+            }
+        }
+    }
+
+    /// Enables or disables the module.
+    /// Returns `false` if the module panicked.
+    fn set_enabled(&mut self, gateway: &Gateway, window: &str, enabled: bool) -> bool {
+        if enabled {
+            self.enable(gateway, window)
+        } else {
+            self.disable(gateway, window)
+        }
+    }
+}
+
+/// Bundles independent modules into a single payload, each with its own section in a shared
+/// window, its own hooks and thread-keys, and its own lifecycle.
+/// Modules can be enabled and disabled at runtime through their `<name>.enabled` thread-key or
+/// `set_enabled`, and are unloaded one by one, either through `unload`, when they panic, or in
+/// reverse order once the payload is ejected.
+/// The registry is a `Payload`, so it can be returned from the function marked with
+/// `#[arctic::gateway]`:
+/// ```ignore
+/// #[arctic::gateway]
+/// fn main(_ctx: &Gateway) -> ModuleRegistry {
+///     ModuleRegistry::new("Arctic").with(Speed::default()).with(Overlay::default())
+/// }
+/// ```
+pub struct ModuleRegistry {
+    /// Window which holds the sections of every module.
+    window: &'static str,
+
+    /// Thread-key which ejects the whole payload once set to `true`.
+    eject_thread_key: Option<&'static str>,
+
+    /// Modules which haven't been unloaded yet, in the order they were added.
+    modules: Vec<Entry>,
+}
+
+impl ModuleRegistry {
+    /// Creates an empty registry, whose modules share the window called `window`.
+    pub fn new(window: &'static str) -> Self {
+        Self {
+            window,
+            eject_thread_key: None,
+            modules: Vec::new(),
+        }
+    }
+
+    /// Ejects the whole payload once the thread-key called `identifier` is set to `true`.
+    pub fn with_eject_thread_key(mut self, identifier: &'static str) -> Self {
+        self.eject_thread_key = Some(identifier);
+        self
+    }
+
+    /// Adds a module, which is loaded along with the registry.
+    /// Modules whose name is already taken are logged and skipped.
+    pub fn with(mut self, module: impl Module) -> Self {
+        let name = module.name();
+        if self.position(name).is_some() {
+            tracing::error!("Module {name} has already been added, skipping it!");
+            return self;
+        }
+
+        self.modules.push(Entry {
+            module: Box::new(module),
+            enable_thread_key: format!("{name}.enabled"),
+            widgets: Vec::new(),
+            enabled: false,
+        });
+        self
+    }
+
+    /// Index of the module called `name`.
+    fn position(&self, name: &str) -> Option<usize> {
+        self.modules
+            .iter()
+            .position(|entry| entry.module.name() == name)
+    }
+
+    /// Names of the modules which haven't been unloaded yet.
+    pub fn modules(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.modules.iter().map(|entry| entry.module.name())
+    }
+
+    /// Checks whether the module called `name` is loaded and enabled.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.position(name)
+            .is_some_and(|index| self.modules[index].enabled)
+    }
+
+    /// Enables or disables the module called `name`, keeping its thread-key in sync.
+    /// Returns `false` if there's no such module.
+    pub fn set_enabled(&mut self, gateway: &Gateway, name: &str, enabled: bool) -> bool {
+        let Some(index) = self.position(name) else {
+            return false;
+        };
+
+        let entry = &mut self.modules[index];
+        set_thread_key_value!(entry.enable_thread_key, enabled);
+        if !entry.set_enabled(gateway, self.window, enabled) {
+            self.unload_at(gateway, index);
+        }
+
+        true
+    }
+
+    /// Disables and unloads the module called `name`, leaving every other module running.
+    /// Returns `false` if there's no such module.
+    pub fn unload(&mut self, gateway: &Gateway, name: &str) -> bool {
+        let Some(index) = self.position(name) else {
+            return false;
+        };
+
+        self.unload_at(gateway, index);
+        true
+    }
+
+    /// Disables and unloads the module at `index`.
+    fn unload_at(&mut self, gateway: &Gateway, index: usize) {
+        let mut entry = self.modules.remove(index);
+        entry.disable(gateway, self.window);
+        entry.call("on_unload", |module| module.on_unload());
+        tracing::info!("Module {} unloaded.", entry.module.name());
+    }
+}
+
+impl Payload for ModuleRegistry {
+    fn on_load(&mut self, gateway: &Gateway) {
+        ui_add_window!(self.window);
+
+        let mut index = 0;
+        while index < self.modules.len() {
+            let entry = &mut self.modules[index];
+            for identifier in entry.module.thread_keys() {
+                create_thread_key!(identifier);
+                set_thread_key_value!(identifier, false);
+            }
+
+            let enabled = entry.module.enabled_by_default();
+            create_thread_key!(entry.enable_thread_key);
+            set_thread_key_value!(entry.enable_thread_key, enabled);
+
+            let loaded = entry.call("on_load", |module| module.on_load(gateway));
+
+            // `enable_hook!` enables hooks right away, which modules starting out disabled mustn't
+            // keep.
+            if loaded && !enabled {
+                entry.disable_hooks();
+            }
+
+            let loaded = loaded && entry.set_enabled(gateway, self.window, enabled);
+
+            if loaded {
+                index += 1;
+            } else {
+                self.unload_at(gateway, index);
+            }
+        }
+    }
+
+    fn on_tick(&mut self, gateway: &Gateway, delta_time: f32) {
+        let mut index = 0;
+        while index < self.modules.len() {
+            let entry = &mut self.modules[index];
+
+            // Hosts without thread-keys keep every module in the state it was loaded in.
+            let mut running = true;
+            if let Ok(enabled) = api::dynamic_get_thread_key(&entry.enable_thread_key) {
+                running = entry.set_enabled(gateway, self.window, enabled);
+            }
+
+            if running && entry.enabled {
+                running = entry.call("on_tick", |module| module.on_tick(gateway, delta_time));
+            }
+
+            if running {
+                index += 1;
+            } else {
+                self.unload_at(gateway, index);
+            }
+        }
+    }
+
+    fn on_pre_eject(&mut self, gateway: &Gateway) {
+        while let Some(index) = self.modules.len().checked_sub(1) {
+            self.unload_at(gateway, index);
+        }
+    }
+
+    fn eject_thread_key(&self) -> Option<&str> {
+        self.eject_thread_key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Call, MockHost, MOCK_PAYLOAD, MOCK_PROCESS};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    struct FakeHook(AtomicBool);
+
+    impl Hook for FakeHook {
+        unsafe fn enable(&self) -> Result<(), String> {
+            self.0.store(true, Ordering::Relaxed);
+            Ok(())
+        }
+
+        unsafe fn disable(&self) -> Result<(), String> {
+            self.0.store(false, Ordering::Relaxed);
+            Ok(())
+        }
+
+        fn is_enabled(&self) -> bool {
+            self.0.load(Ordering::Relaxed)
+        }
+    }
+
+    /// Module whose hook is enabled in `on_load`, the way `enable_hook!` does it.
+    struct Hooked {
+        name: &'static str,
+        enabled_by_default: bool,
+        hook: &'static FakeHook,
+    }
+
+    impl Hooked {
+        fn new(name: &'static str, enabled_by_default: bool) -> Self {
+            Self {
+                name,
+                enabled_by_default,
+                hook: Box::leak(Box::new(FakeHook(AtomicBool::new(false)))),
+            }
+        }
+    }
+
+    impl Module for Hooked {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn enabled_by_default(&self) -> bool {
+            self.enabled_by_default
+        }
+
+        fn hooks(&self) -> Vec<(&'static str, &'static dyn Hook)> {
+            vec![("hook", self.hook)]
+        }
+
+        fn on_load(&mut self, _gateway: &Gateway) {
+            // SAFETY: The hook is fake.
+            unsafe { self.hook.enable() }.unwrap();
+        }
+
+        fn build_ui(&mut self, section: &mut Section) {
+            section.label("title", "Own title", 0);
+        }
+    }
+
+    /// Gateway running against `host`.
+    fn gateway(host: &MockHost) -> &'static Gateway {
+        // SAFETY: The mock's table outlives the test.
+        unsafe { crate::gateway::start(MOCK_PROCESS, MOCK_PAYLOAD, host.table()) }.unwrap()
+    }
+
+    #[test]
+    fn modules_starting_disabled_have_their_hooks_disabled() {
+        let host = MockHost::new();
+        let gateway = gateway(&host);
+        let on = Hooked::new("on", true);
+        let off = Hooked::new("off", false);
+        let (on_hook, off_hook) = (on.hook, off.hook);
+
+        let mut registry = ModuleRegistry::new("Arctic").with(on).with(off);
+        registry.on_load(gateway);
+        assert!(registry.is_enabled("on") && on_hook.is_enabled());
+        assert!(!registry.is_enabled("off") && !off_hook.is_enabled());
+        assert_eq!(host.thread_key("off.enabled"), Some(false));
+
+        registry.set_enabled(gateway, "off", true);
+        assert!(off_hook.is_enabled());
+        registry.on_pre_eject(gateway);
+        assert!(!on_hook.is_enabled() && !off_hook.is_enabled());
+    }
+
+    #[test]
+    fn sections_use_distinct_identifiers() {
+        let host = MockHost::new();
+        let gateway = gateway(&host);
+        let mut registry = ModuleRegistry::new("Arctic")
+            .with(Hooked::new("first", true))
+            .with(Hooked::new("second", true));
+        registry.on_load(gateway);
+
+        let widgets = host
+            .calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                Call::AddLabel { identifier, .. } => Some(identifier),
+                Call::AddSeparator(identifier) => Some(identifier),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            widgets,
+            [
+                "first#title",
+                "first.title",
+                "first#end",
+                "second#title",
+                "second.title",
+                "second#end",
+            ]
+        );
+    }
+}
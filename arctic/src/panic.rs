@@ -0,0 +1,85 @@
+use crate::{gateway::Gateway, logging};
+use std::{backtrace::Backtrace, panic::AssertUnwindSafe, sync::Once};
+
+/// What happens once a panic has been contained, see `Gateway::set_panic_policy`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Winds the payload down and ejects it cleanly, through `Gateway::request_eject`.
+    #[default]
+    Eject,
+
+    /// Logs the panic and keeps running, skipping whatever panicked.
+    Continue,
+}
+
+/// Extracts the message of a panic payload.
+pub(crate) fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("<non-string panic payload>")
+}
+
+/// Installs a panic hook which forwards the message, location and backtrace of every panic to
+/// `dynamic_log`, since the host's `stderr` is rarely visible. The previous hook still runs
+/// afterwards.
+pub(crate) fn install_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let location = info
+                .location()
+                .map_or_else(|| "<unknown location>".to_owned(), ToString::to_string);
+
+            tracing::error!(
+                "Panic at {location}: {}\nBacktrace:\n{}",
+                panic_message(info.payload()),
+                Backtrace::force_capture()
+            );
+            logging::flush();
+            previous(info);
+        }));
+    });
+}
+
+/// Runs `callback` with panics contained, returning the panic message if it panicked.
+pub fn catch_panic<T>(callback: impl FnOnce() -> T) -> Result<T, String> {
+    std::panic::catch_unwind(AssertUnwindSafe(callback))
+        .map_err(|panic| panic_message(&*panic).to_owned())
+}
+
+/// Logs a contained panic and applies the gateway's `PanicPolicy`.
+pub(crate) fn handle(gateway: &Gateway, context: &str, message: &str) {
+    match gateway.panic_policy() {
+        PanicPolicy::Eject => {
+            tracing::error!("Payload panicked in {context}, ejecting! Message: {message}");
+            gateway.request_eject();
+        }
+        PanicPolicy::Continue => {
+            tracing::error!("Payload panicked in {context}, continuing! Message: {message}")
+        }
+    }
+}
+
+/// Runs `callback` with panics contained, handling them according to the `PanicPolicy`.
+pub(crate) fn contain(gateway: &Gateway, context: &str, callback: impl FnOnce()) {
+    if let Err(message) = catch_panic(callback) {
+        handle(gateway, context, &message);
+    }
+}
+
+/// Runs `callback` with panics contained, for code which is called by the host or the game such as
+/// hook closures, where unwinding would take the process down.
+/// Panics are handled according to the `PanicPolicy`, and `fallback` provides the value returned
+/// instead, for example by calling the hooked function.
+pub fn guard<T>(context: &str, callback: impl FnOnce() -> T, fallback: impl FnOnce() -> T) -> T {
+    catch_panic(callback).unwrap_or_else(|message| {
+        if let Some(gateway) = Gateway::get() {
+            handle(gateway, context, &message);
+        }
+
+        fallback()
+    })
+}
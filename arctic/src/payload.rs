@@ -0,0 +1,158 @@
+use crate::{gateway::Gateway, panic::contain};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Tick interval used when the host doesn't report a usable delta-time.
+const FALLBACK_TICK_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Shortest and longest time to wait between two ticks, so that a bogus delta-time neither spins
+/// the tick thread nor stalls it.
+const MIN_TICK_INTERVAL: Duration = Duration::from_millis(1);
+const MAX_TICK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A payload which keeps running after its main function returned.
+/// Return it from the function marked with `#[arctic::gateway]`, and the gateway drives it on its
+/// own thread until it's ejected:
+/// 1. `on_load`, once.
+/// 2. `on_tick`, once per tick until ejection is requested through `Gateway::request_eject`, the
+///    thread-key from `eject_thread_key`, or a panic.
+/// 3. `on_pre_eject`, while the host is still fully usable.
+/// 4. `on_unload`, right before dynamic is asked to eject the payload.
+///
+/// Every callback runs with panics contained, which are handled according to the `PanicPolicy`.
+pub trait Payload: Send + 'static {
+    /// Called once on the tick thread, before the first tick.
+    fn on_load(&mut self, _gateway: &Gateway) {}
+
+    /// Called once per tick, with the delta-time reported by dynamic.
+    fn on_tick(&mut self, _gateway: &Gateway, _delta_time: f32) {}
+
+    /// Called once ejection has been requested, for removing widgets, disabling hooks and
+    /// anything else which needs the host.
+    fn on_pre_eject(&mut self, _gateway: &Gateway) {}
+
+    /// Called last, for releasing anything the payload owns.
+    /// Threads the payload spawned have to be joined here, otherwise a hot-reload shim keeps the
+    /// old version loaded rather than unmapping code they're running.
+    fn on_unload(&mut self) {}
+
+    /// Thread-key which requests ejection once set to `true`, created by the gateway before
+    /// `on_load`.
+    fn eject_thread_key(&self) -> Option<&str> {
+        None
+    }
+
+    /// Serializes whatever should survive a hot reload, called on the old version of the payload
+    /// right before `on_unload`.
+    fn save_state(&mut self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restores the state saved by the previous version of the payload, called before `on_load`
+    /// after a hot reload.
+    fn restore_state(&mut self, _state: &[u8]) {}
+}
+
+/// What the function marked with `#[arctic::gateway]` may return.
+pub trait MainReturn {
+    /// Takes over once the main function has returned.
+    fn start(self, gateway: &'static Gateway);
+
+    /// Hands the payload over to a hot-reload shim instead of driving it, `None` for one-shot
+    /// payloads.
+    fn into_payload(self) -> Option<Box<dyn Payload>>;
+}
+
+/// One-shot payloads, which are ejected as soon as their main function returns, unless they've
+/// called `Gateway::stay_resident`.
+impl MainReturn for () {
+    fn start(self, gateway: &'static Gateway) {
+        if !gateway.is_resident() {
+            gateway.eject();
+        }
+    }
+
+    fn into_payload(self) -> Option<Box<dyn Payload>> {
+        None
+    }
+}
+
+impl<P: Payload> MainReturn for P {
+    fn start(self, gateway: &'static Gateway) {
+        gateway.stay_resident();
+        std::thread::spawn(move || drive(self, gateway));
+    }
+
+    fn into_payload(self) -> Option<Box<dyn Payload>> {
+        Some(Box::new(self))
+    }
+}
+
+/// Creates and resets the eject thread-key, then calls `on_load`.
+pub(crate) fn load(payload: &mut dyn Payload, gateway: &Gateway) {
+    if let Some(identifier) = payload.eject_thread_key() {
+        create_thread_key!(identifier);
+        set_thread_key_value!(identifier, false);
+    }
+
+    contain(gateway, "on_load", || payload.on_load(gateway));
+}
+
+/// See `ticks`.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Number of ticks started so far, `0` until a `Payload` ticks for the first time.
+/// Used for caching values which only change from one tick to the next.
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Acquire)
+}
+
+/// Polls the eject thread-key and calls `on_tick`.
+/// Returns `false` once ejection has been requested.
+pub(crate) fn tick(payload: &mut dyn Payload, gateway: &Gateway, delta_time: f32) -> bool {
+    TICKS.fetch_add(1, Ordering::AcqRel);
+
+    if gateway.is_eject_requested() {
+        return false;
+    }
+
+    if payload
+        .eject_thread_key()
+        .is_some_and(|identifier| get_thread_key!(identifier))
+    {
+        gateway.request_eject();
+        return false;
+    }
+
+    contain(gateway, "on_tick", || payload.on_tick(gateway, delta_time));
+    !gateway.is_eject_requested()
+}
+
+/// Drives `payload` through its lifecycle, see `Payload`.
+fn drive(mut payload: impl Payload, gateway: &'static Gateway) {
+    load(&mut payload, gateway);
+    loop {
+        let delta_time = get_delta_time!();
+        if !tick(&mut payload, gateway, delta_time) {
+            break;
+        }
+
+        std::thread::sleep(tick_interval(delta_time));
+    }
+
+    contain(gateway, "on_pre_eject", || payload.on_pre_eject(gateway));
+    contain(gateway, "on_unload", || payload.on_unload());
+    gateway.eject();
+}
+
+/// Time to wait until the next tick, roughly one frame.
+fn tick_interval(delta_time: f32) -> Duration {
+    Duration::try_from_secs_f32(delta_time)
+        .ok()
+        .filter(|interval| !interval.is_zero())
+        .map_or(FALLBACK_TICK_INTERVAL, |interval| {
+            interval.clamp(MIN_TICK_INTERVAL, MAX_TICK_INTERVAL)
+        })
+}
@@ -0,0 +1,312 @@
+use crate::{
+    gateway::Gateway,
+    hot_reload,
+    memory::{Refinement, Scannable, ValueScan, MAX_CANDIDATES},
+    modules::{Module, Section},
+};
+use std::sync::{
+    mpsc::{self, Receiver, TryRecvError},
+    Mutex, PoisonError,
+};
+
+/// Most candidates listed in the results window.
+const MAX_LISTED: usize = 64;
+
+/// Value which can be entered through a slider, see `ValueScanner`.
+pub trait SliderValue: Scannable + Send + 'static {
+    /// Adds a slider for entering the value, see `Section::i32_slider`.
+    fn add_slider(
+        section: &mut Section,
+        identifier: &str,
+        text: &str,
+        min: Self,
+        max: Self,
+    ) -> String;
+
+    /// Current value of the slider called `identifier`.
+    fn slider_value(identifier: &str) -> Self;
+}
+
+impl SliderValue for i32 {
+    fn add_slider(
+        section: &mut Section,
+        identifier: &str,
+        text: &str,
+        min: i32,
+        max: i32,
+    ) -> String {
+        section.i32_slider(identifier, text, min, max, None)
+    }
+
+    fn slider_value(identifier: &str) -> Self {
+        get_i32_slider_value!(identifier)
+    }
+}
+
+impl SliderValue for f32 {
+    fn add_slider(
+        section: &mut Section,
+        identifier: &str,
+        text: &str,
+        min: f32,
+        max: f32,
+    ) -> String {
+        section.f32_slider(identifier, text, min, max, None)
+    }
+
+    fn slider_value(identifier: &str) -> Self {
+        get_f32_slider_value!(identifier)
+    }
+}
+
+/// What a button of the scanner asked for, carried out on the next tick.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Action {
+    First,
+    Equals,
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    Reset,
+}
+
+/// Action requested through a button, since button callbacks can't carry any state.
+static REQUESTED: Mutex<Option<Action>> = Mutex::new(None);
+
+fn request(action: Action) {
+    *REQUESTED.lock().unwrap_or_else(PoisonError::into_inner) = Some(action);
+}
+
+/// Module for rediscovering the addresses of values in-game, like Cheat Engine: enter the current
+/// value through the slider and run a first scan, then narrow the candidates down through the
+/// refinement buttons as the value changes in the game.
+/// The candidates are listed in a window of their own, `Value Scanner` by default.
+/// First scans go through the whole process, so they run on a thread of their own rather than
+/// stalling the game, and the window shows the candidates once they're done.
+/// Only a single scanner can be added to a registry.
+/// ```ignore
+/// ModuleRegistry::new("Arctic").with(ValueScanner::<i32>::new(0, 10_000))
+/// ```
+pub struct ValueScanner<T> {
+    /// Range of the value slider.
+    min: T,
+    max: T,
+
+    /// Window which lists the candidates.
+    window: &'static str,
+
+    /// Full identifier of the value slider, once it's been added.
+    slider: String,
+
+    scan: Option<ValueScan<T>>,
+
+    /// Receives the result of the running first scan, if any.
+    scanning: Option<Receiver<ValueScan<T>>>,
+}
+
+impl<T: SliderValue> ValueScanner<T> {
+    /// Scanner whose value slider ranges from `min` to `max`.
+    pub fn new(min: T, max: T) -> Self {
+        Self {
+            min,
+            max,
+            window: "Value Scanner",
+            slider: String::new(),
+            scan: None,
+            scanning: None,
+        }
+    }
+
+    /// Lists the candidates in the window called `window` instead.
+    pub fn with_window(mut self, window: &'static str) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// The current scan, `None` until the first scan.
+    pub fn scan(&self) -> Option<&ValueScan<T>> {
+        self.scan.as_ref()
+    }
+
+    /// Carries out `action`, with `value` taken from the slider.
+    fn apply(&mut self, action: Action, value: T) {
+        if self.scanning.is_some() {
+            tracing::warn!("Wait for the first scan to finish.");
+            return;
+        }
+
+        let refinement = match action {
+            Action::First => {
+                let (sender, receiver) = mpsc::channel();
+                hot_reload::spawn(move || {
+                    let _ = sender.send(ValueScan::first(value));
+                });
+                self.scan = None;
+                self.scanning = Some(receiver);
+                return;
+            }
+            Action::Reset => {
+                self.scan = None;
+                return;
+            }
+            Action::Equals => Refinement::Equals(value),
+            Action::Changed => Refinement::Changed,
+            Action::Unchanged => Refinement::Unchanged,
+            Action::Increased => Refinement::Increased,
+            Action::Decreased => Refinement::Decreased,
+        };
+
+        match &mut self.scan {
+            Some(scan) => scan.refine(refinement),
+            None => tracing::warn!("Run a first scan before refining it."),
+        }
+    }
+
+    /// Takes the result of the running first scan over once it's done, returning whether it is.
+    fn receive_scan(&mut self) -> bool {
+        let Some(scanning) = &self.scanning else {
+            return false;
+        };
+
+        match scanning.try_recv() {
+            Ok(scan) => self.scan = Some(scan),
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Disconnected) => tracing::error!("The first scan failed!"),
+        }
+
+        self.scanning = None;
+        true
+    }
+
+    /// Lists the candidates in the results window.
+    fn show_results(&self) {
+        ui_focus_window!(self.window);
+        ui_remove_all_widgets!();
+        if self.scanning.is_some() {
+            ui_add_label!("value_scanner.summary", "Scanning…", 0);
+            return;
+        }
+
+        let Some(scan) = &self.scan else {
+            ui_add_label!(
+                "value_scanner.summary",
+                "Enter a value and run a first scan.",
+                0
+            );
+            return;
+        };
+
+        let truncated = if scan.is_truncated() {
+            format!(", stopped at the first {MAX_CANDIDATES}")
+        } else {
+            String::new()
+        };
+
+        ui_add_label!(
+            "value_scanner.summary",
+            format_args!("{} candidates{truncated}", scan.len()),
+            0
+        );
+
+        for (index, (address, value)) in scan.candidates().iter().take(MAX_LISTED).enumerate() {
+            ui_add_label!(
+                format_args!("value_scanner.candidate.{index}"),
+                format_args!("{:#x} = {value}", address.get()),
+                0
+            );
+        }
+
+        if let Some(more) = scan.len().checked_sub(MAX_LISTED).filter(|more| *more > 0) {
+            ui_add_label!("value_scanner.more", format_args!("… and {more} more"), 0);
+        }
+    }
+}
+
+impl<T: SliderValue> Module for ValueScanner<T> {
+    fn name(&self) -> &'static str {
+        "value_scanner"
+    }
+
+    fn on_load(&mut self, _gateway: &Gateway) {
+        ui_add_window!(self.window);
+        *REQUESTED.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    fn build_ui(&mut self, section: &mut Section) {
+        self.slider = T::add_slider(section, "value", "Value", self.min, self.max);
+        section.button(
+            "first",
+            "First scan",
+            "",
+            button_callback!(|| request(Action::First)),
+        );
+        section.same_line("first.same_line");
+        section.button(
+            "reset",
+            "Reset",
+            "",
+            button_callback!(|| request(Action::Reset)),
+        );
+
+        section.button(
+            "equals",
+            "Equals",
+            "",
+            button_callback!(|| request(Action::Equals)),
+        );
+        section.same_line("equals.same_line");
+        section.button(
+            "changed",
+            "Changed",
+            "",
+            button_callback!(|| request(Action::Changed)),
+        );
+        section.same_line("changed.same_line");
+        section.button(
+            "unchanged",
+            "Unchanged",
+            "",
+            button_callback!(|| request(Action::Unchanged)),
+        );
+        section.same_line("unchanged.same_line");
+        section.button(
+            "increased",
+            "Increased",
+            "",
+            button_callback!(|| request(Action::Increased)),
+        );
+        section.same_line("increased.same_line");
+        section.button(
+            "decreased",
+            "Decreased",
+            "",
+            button_callback!(|| request(Action::Decreased)),
+        );
+    }
+
+    fn on_enable(&mut self, _gateway: &Gateway) {
+        self.show_results();
+    }
+
+    fn on_tick(&mut self, _gateway: &Gateway, _delta_time: f32) {
+        let requested = REQUESTED
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        let mut changed = self.receive_scan();
+        if let Some(action) = requested {
+            self.apply(action, T::slider_value(&self.slider));
+            changed = true;
+        }
+
+        if changed {
+            self.show_results();
+        }
+    }
+
+    fn on_disable(&mut self, _gateway: &Gateway) {
+        ui_focus_window!(self.window);
+        ui_remove_all_widgets!();
+    }
+}